@@ -0,0 +1,219 @@
+use super::{Block, BlockError, Blockchain};
+use std::collections::{HashSet, VecDeque};
+
+impl Blockchain {
+    // 2/3 of the active delegates plus one.
+    pub fn irreversibility_threshold(&self) -> usize {
+        self.delegates.len() * 2 / 3 + 1
+    }
+
+    // A producer building on a block confirms it and every ancestor back to the last
+    // irreversible block. The highest block confirmed by enough delegates becomes irreversible.
+    pub fn confirm_ancestors(&mut self, block: &Block) {
+        for ancestor in self.chain.iter().filter(|ancestor| ancestor.id > self.last_irreversible) {
+            self.confirmations
+                .entry(ancestor.id)
+                .or_default()
+                .insert(block.validator.clone());
+        }
+        self.confirmations
+            .entry(block.id)
+            .or_default()
+            .insert(block.validator.clone());
+
+        let threshold = self.irreversibility_threshold();
+        if let Some(id) = self
+            .confirmations
+            .iter()
+            .filter(|(_, confirmed_by)| confirmed_by.len() >= threshold)
+            .map(|(id, _)| *id)
+            .max()
+        {
+            if id > self.last_irreversible {
+                self.last_irreversible = id;
+                self.confirmations.retain(|block_id, _| *block_id > id);
+            }
+        }
+    }
+
+    // Switches to a longer fork, unless that would undo an irreversible block. The fork's blocks
+    // are replayed through `append_block` from the fork point, so each one must come from its
+    // slot's delegate; confirmations, the last irreversible block, production stats and
+    // transferred stake are rebuilt from the new branch. Returns whether we switched.
+    pub fn switch_to_fork(&mut self, fork: VecDeque<Block>) -> Result<bool, BlockError> {
+        let fork_point = self
+            .chain
+            .iter()
+            .zip(fork.iter())
+            .take_while(|(ours, theirs)| ours.hash == theirs.hash)
+            .last()
+            .map(|(ours, _)| ours.id)
+            .ok_or(BlockError::UnknownParent(0))?;
+        if fork_point < self.last_irreversible {
+            return Err(BlockError::BelowIrreversible {
+                fork_point,
+                last_irreversible: self.last_irreversible,
+            });
+        }
+        if fork.len() <= self.chain.len() {
+            return Ok(false);
+        }
+
+        let saved = (
+            self.chain.clone(),
+            self.confirmations.clone(),
+            self.last_irreversible,
+            self.production.clone(),
+            self.nodes.clone(),
+        );
+        let discarded: Vec<Block> = self.chain.iter().filter(|block| block.id > fork_point).cloned().collect();
+        for block in discarded.iter().rev() {
+            if let Some(stats) = self.production.get_mut(&block.validator) {
+                stats.produced -= 1;
+            }
+            self.revert_transfers(block);
+        }
+        self.chain.retain(|block| block.id <= fork_point);
+        self.rebuild_confirmations();
+        for block in fork.into_iter().filter(|block| block.id > fork_point) {
+            if let Err(err) = self.append_block(block) {
+                (self.chain, self.confirmations, self.last_irreversible, self.production, self.nodes) = saved;
+                return Err(err);
+            }
+        }
+        self.revalidate_pending_transfers();
+        Ok(true)
+    }
+
+    // Confirmations above the irreversible block, as implied by the producers on our chain.
+    fn rebuild_confirmations(&mut self) {
+        self.confirmations.clear();
+        for block in self.chain.iter().filter(|block| block.id > self.last_irreversible) {
+            let confirmed_by: HashSet<String> = self
+                .chain
+                .iter()
+                .filter(|later| later.id >= block.id)
+                .map(|later| later.validator.clone())
+                .collect();
+            self.confirmations.insert(block.id, confirmed_by);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    // Three elected delegates have produced four blocks, the last one carrying a stake transfer,
+    // then two slots pass without a block.
+    fn blockchain_with_gap() -> (Blockchain, [u64; 2]) {
+        let mut blockchain = Blockchain::new();
+        for (name, stake) in [("Alice", 30), ("Bob", 20), ("Charlie", 10)] {
+            blockchain.register_node(Node::new(name, stake));
+            blockchain.vote_for_delegate(name, &[name]);
+        }
+        blockchain.elect_delegates(3);
+        for _ in 0..3 {
+            blockchain.finalize_pending_transactions();
+        }
+        blockchain.transfer_stake("Alice", "Charlie", 5).unwrap();
+        blockchain.finalize_pending_transactions();
+        let slots = [blockchain.advance_slot(), blockchain.advance_slot()];
+        (blockchain, slots)
+    }
+
+    // A branch from just below the head, with a block for each slot sealed by `producer`.
+    fn fork(blockchain: &Blockchain, slots: [u64; 2], producer: impl Fn(u64) -> String) -> VecDeque<Block> {
+        let mut fork: VecDeque<Block> = blockchain.chain.iter().take(blockchain.chain.len() - 1).cloned().collect();
+        for slot in slots {
+            let parent = fork.back().unwrap();
+            fork.push_back(Block::new(parent.id + 1, slot, parent.hash.clone(), vec![], producer(slot)));
+        }
+        fork
+    }
+
+    fn total_produced(blockchain: &Blockchain) -> u64 {
+        blockchain.production.values().map(|stats| stats.produced).sum()
+    }
+
+    #[test]
+    fn fork_out_of_slot_is_refused_and_state_kept() {
+        let (mut blockchain, slots) = blockchain_with_gap();
+        let head = blockchain.chain.back().unwrap().hash.clone();
+        let irreversible = blockchain.last_irreversible;
+        let scheduled = blockchain.producer_for_slot(slots[1]).unwrap();
+        let intruder = blockchain.delegates.iter().find(|delegate| **delegate != scheduled).unwrap().clone();
+        let fork = fork(&blockchain, slots, |slot| {
+            if slot == slots[1] {
+                intruder.clone()
+            } else {
+                blockchain.producer_for_slot(slot).unwrap()
+            }
+        });
+
+        assert!(matches!(blockchain.switch_to_fork(fork), Err(BlockError::OutOfSlot { .. })));
+        assert_eq!(blockchain.chain.back().unwrap().hash, head);
+        assert_eq!(blockchain.last_irreversible, irreversible);
+        assert_eq!(total_produced(&blockchain), 4);
+        assert_eq!(blockchain.nodes["Alice"].stake, 25);
+    }
+
+    #[test]
+    fn fork_in_the_future_is_refused() {
+        let (mut blockchain, slots) = blockchain_with_gap();
+        let future = [slots[1], slots[1] + 1];
+        let fork = fork(&blockchain, future, |slot| blockchain.producer_for_slot(slot).unwrap());
+        assert!(matches!(blockchain.switch_to_fork(fork), Err(BlockError::InvalidSlot(_))));
+    }
+
+    #[test]
+    fn fork_drops_pending_transfers_it_no_longer_funds() {
+        let (mut blockchain, slots) = blockchain_with_gap();
+        // Charlie's queued transfer spends the stake he received in the head block.
+        blockchain.transfer_stake("Charlie", "Bob", 15).unwrap();
+        blockchain.transfer_stake("Alice", "Bob", 25).unwrap();
+        let fork = fork(&blockchain, slots, |slot| blockchain.producer_for_slot(slot).unwrap());
+        assert!(blockchain.switch_to_fork(fork).unwrap());
+        assert_eq!(blockchain.pending_transfers.len(), 1);
+        assert_eq!(blockchain.pending_transfers[0].sender, "Alice");
+
+        let length = blockchain.chain.len();
+        blockchain.finalize_pending_transactions();
+        assert_eq!(blockchain.chain.len(), length + 1);
+        assert!(blockchain.pending_transfers.is_empty());
+        assert_eq!(blockchain.nodes["Bob"].stake, 45);
+    }
+
+    #[test]
+    fn shorter_fork_is_ignored() {
+        let (mut blockchain, slots) = blockchain_with_gap();
+        let head = blockchain.chain.back().unwrap().hash.clone();
+        let mut fork = fork(&blockchain, slots, |slot| blockchain.producer_for_slot(slot).unwrap());
+        fork.pop_back();
+        assert!(!blockchain.switch_to_fork(fork).unwrap());
+        assert_eq!(blockchain.chain.back().unwrap().hash, head);
+    }
+
+    #[test]
+    fn valid_fork_rebuilds_stats_and_irreversibility() {
+        let (mut blockchain, slots) = blockchain_with_gap();
+        let discarded = blockchain.chain.back().unwrap().validator.clone();
+        let produced_before = blockchain.production[&discarded].produced;
+        let fork = fork(&blockchain, slots, |slot| blockchain.producer_for_slot(slot).unwrap());
+        let producers: Vec<String> = fork.iter().rev().take(2).map(|block| block.validator.clone()).collect();
+
+        assert!(blockchain.switch_to_fork(fork).unwrap());
+        assert_eq!(blockchain.chain.len(), 6);
+        assert_eq!(total_produced(&blockchain), 5);
+        let expected = produced_before - 1 + producers.iter().filter(|name| **name == discarded).count() as u64;
+        assert_eq!(blockchain.production[&discarded].produced, expected);
+        // The transfer was in the discarded head, so Alice gets her stake back.
+        assert_eq!(blockchain.nodes["Alice"].stake, 30);
+        assert_eq!(blockchain.nodes["Charlie"].votes, 10);
+        // Every block confirmed by all three delegates is irreversible.
+        let head = blockchain.chain.back().unwrap().id;
+        assert!(blockchain.last_irreversible > 0 && blockchain.last_irreversible < head);
+        assert!(blockchain.confirmations.keys().all(|id| *id > blockchain.last_irreversible));
+    }
+}
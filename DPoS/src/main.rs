@@ -1,9 +1,20 @@
+mod irreversible;
+mod reliability;
+mod schedule;
+mod stake;
+mod voting;
+
 use chrono::{Utc};
 use sha2::{Sha256, Digest};
-use std::collections::{HashMap, VecDeque};
+use reliability::ProductionStats;
+use schedule::Round;
+use stake::StakeTransfer;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
+use voting::VoteWeighting;
 
 #[derive(Debug, Clone)]
 struct Block {
@@ -11,29 +22,34 @@ struct Block {
     timestamp: String,
     previous_hash: String,
     transactions: Vec<String>,
+    transfers: Vec<StakeTransfer>,
     hash: String,
     validator: String,
+    slot: u64,
 }
 
 impl Block {
-    fn new(id: u64, previous_hash: String, transactions: Vec<String>, validator: String) -> Self {
+    fn new(id: u64, slot: u64, previous_hash: String, transactions: Vec<String>, validator: String) -> Self {
         let timestamp = Utc::now().to_rfc3339();
         let mut block = Block {
             id,
             timestamp,
             previous_hash,
             transactions,
+            transfers: vec![],
             hash: String::new(),
             validator,
+            slot,
         };
         block.finalize_block();
         block
     }
 
     fn calculate_hash(&self) -> String {
+        let transfers: String = self.transfers.iter().map(|transfer| transfer.to_string()).collect();
         let data = format!(
-            "{}{}{}{}{}",
-            self.id, self.timestamp, self.previous_hash, self.validator, self.transactions.join("")
+            "{}{}{}{}{}{}{}",
+            self.id, self.slot, self.timestamp, self.previous_hash, self.validator, self.transactions.join(""), transfers
         );
         let mut hasher = Sha256::new();
         hasher.update(data);
@@ -45,6 +61,35 @@ impl Block {
     }
 }
 
+#[derive(Debug)]
+enum BlockError {
+    UnknownParent(u64),
+    InvalidHash(u64),
+    InvalidSlot(u64),
+    InvalidTransfer(u64),
+    OutOfSlot { slot: u64, producer: String, expected: String },
+    BelowIrreversible { fork_point: u64, last_irreversible: u64 },
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockError::UnknownParent(id) => write!(f, "block {} does not extend the chain", id),
+            BlockError::InvalidHash(id) => write!(f, "block {} has an invalid hash", id),
+            BlockError::InvalidSlot(slot) => write!(f, "slot {} is not after the parent or is in the future", slot),
+            BlockError::InvalidTransfer(id) => write!(f, "block {} transfers more stake than a sender holds", id),
+            BlockError::OutOfSlot { slot, producer, expected } => {
+                write!(f, "{} produced in slot {} which belongs to {}", producer, slot, expected)
+            }
+            BlockError::BelowIrreversible { fork_point, last_irreversible } => write!(
+                f,
+                "fork from block {} would revert irreversible block {}",
+                fork_point, last_irreversible
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Node {
     name: String,
@@ -65,18 +110,47 @@ impl Node {
 struct Blockchain {
     chain: VecDeque<Block>,
     pending_transactions: Vec<String>,
+    pending_transfers: Vec<StakeTransfer>,
     nodes: HashMap<String, Node>,
     delegates: Vec<String>,
+    // Each voter's current approvals: voter -> delegates.
+    vote_records: HashMap<String, Vec<String>>,
+    max_approvals: usize,
+    weighting: VoteWeighting,
+    schedule_seed: u64,
+    current_slot: u64,
+    // Every round since the first slot, so past slots validate against the delegates of their round.
+    rounds: Vec<Round>,
+    offline: HashSet<String>,
+    production: HashMap<String, ProductionStats>,
+    max_consecutive_missed: u64,
+    dropped: HashSet<String>,
+    // Delegates that have built on each block above the last irreversible one.
+    confirmations: HashMap<u64, HashSet<String>>,
+    last_irreversible: u64,
 }
 
 impl Blockchain {
     fn new() -> Self {
-        let genesis_block = Block::new(0, String::from("0"), vec!["Genesis Block".to_string()], "System".to_string());
+        let genesis_block = Block::new(0, 0, String::from("0"), vec!["Genesis Block".to_string()], "System".to_string());
         let mut blockchain = Blockchain {
             chain: VecDeque::new(),
             pending_transactions: vec![],
+            pending_transfers: vec![],
             nodes: HashMap::new(),
             delegates: vec![],
+            vote_records: HashMap::new(),
+            max_approvals: 3,
+            weighting: VoteWeighting::Full,
+            schedule_seed: 42,
+            current_slot: 0,
+            rounds: vec![],
+            offline: HashSet::new(),
+            production: HashMap::new(),
+            max_consecutive_missed: 3,
+            dropped: HashSet::new(),
+            confirmations: HashMap::new(),
+            last_irreversible: 0,
         };
         blockchain.chain.push_back(genesis_block);
         blockchain
@@ -86,30 +160,47 @@ impl Blockchain {
         self.nodes.insert(node.name.clone(), node);
     }
 
-    fn vote_for_delegate(&mut self, voter: &str, delegate: &str) {
-        let voter_stake = if let Some(voter_node) = self.nodes.get(voter) {
-            voter_node.stake
-        } else {
+    // Approves up to `max_approvals` delegates. Voting again replaces the voter's previous
+    // approvals instead of adding to them.
+    fn vote_for_delegate(&mut self, voter: &str, delegates: &[&str]) {
+        if !self.nodes.contains_key(voter) {
             println!("Voter {} not found.", voter);
             return;
-        };
-
-        if let Some(delegate_node) = self.nodes.get_mut(delegate) {
-            delegate_node.votes += voter_stake; // Voter'ın stake'i kadar oy eklenir
-            println!("{} voted for {}", voter, delegate);
-        } else {
-            println!("Delegate {} not found.", delegate);
         }
+        let mut approved: Vec<String> = vec![];
+        for delegate in delegates {
+            if !self.nodes.contains_key(*delegate) {
+                println!("Delegate {} not found.", delegate);
+                return;
+            }
+            if !approved.iter().any(|name| name == delegate) {
+                approved.push(delegate.to_string());
+            }
+        }
+        if approved.len() > self.max_approvals {
+            println!("{} cannot approve more than {} delegates.", voter, self.max_approvals);
+            return;
+        }
+
+        self.vote_records.insert(voter.to_string(), approved);
+        self.recompute_tallies();
+        println!("{} voted for {:?}", voter, delegates);
     }
 
+    // Tallies are recomputed from current balances, so votes carry the voter's stake at election time.
     fn elect_delegates(&mut self, num_delegates: usize) {
+        self.recompute_tallies();
         let mut candidates: Vec<_> = self.nodes.values().collect();
-        candidates.sort_by(|a, b| b.votes.cmp(&a.votes)); // En çok oy alanları sıralar
+        candidates.sort_by_key(|node| (std::cmp::Reverse(node.votes), node.name.clone())); // En çok oy alanları sıralar
         self.delegates = candidates
             .into_iter()
             .take(num_delegates)
             .map(|node| node.name.clone())
             .collect();
+        // The next slot starts a fresh round for the new delegate set, and delegates dropped
+        // for missing slots may stand again.
+        self.end_round();
+        self.dropped.clear();
         println!("Elected delegates: {:?}", self.delegates);
     }
 
@@ -123,16 +214,29 @@ impl Blockchain {
             return;
         }
 
-        let validator = self.delegates[0].clone(); // İlk delege blokları üretir
+        let slot = self.advance_slot();
+        let validator = self.producer_for_slot(slot).expect("every slot has a producer"); // Sıradaki delege blok üretir
+        if self.offline.contains(&validator) {
+            self.record_missed_slot(slot, &validator);
+            return;
+        }
         let last_block = self.chain.back().expect("Blockchain is empty; no last block found.");
-        let new_block = Block::new(
+        let mut new_block = Block::new(
             last_block.id + 1,
+            slot,
             last_block.hash.clone(),
             self.pending_transactions.clone(),
-            validator,
+            validator.clone(),
         );
-        self.chain.push_back(new_block);
+        new_block.transfers = self.pending_transfers.clone();
+        new_block.finalize_block();
+        if let Err(err) = self.append_block(new_block) {
+            println!("Block rejected: {}", err);
+            return;
+        }
         self.pending_transactions.clear();
+        self.pending_transfers.clear();
+        println!("Slot {}: block produced by {}", slot, validator);
     }
 
     fn print_chain(&self) {
@@ -151,6 +255,7 @@ fn main() {
         Node::new("Bob", 30),
         Node::new("Charlie", 20),
         Node::new("David", 10),
+        Node::new("Erin", 5),
     ];
 
     {
@@ -163,10 +268,33 @@ fn main() {
     // Oy verme işlemi
     {
         let mut blockchain = blockchain.lock().unwrap();
-        blockchain.vote_for_delegate("Alice", "Bob");
-        blockchain.vote_for_delegate("Charlie", "Bob");
-        blockchain.vote_for_delegate("David", "Alice");
-        blockchain.elect_delegates(2); // En çok oy alan 2 kişi seçiliyor
+        blockchain.vote_for_delegate("Alice", &["Bob"]);
+        blockchain.vote_for_delegate("Charlie", &["Bob", "Alice"]);
+        blockchain.vote_for_delegate("David", &["Alice"]);
+        // Re-voting replaces the earlier vote, so Bob's tally does not grow.
+        blockchain.vote_for_delegate("Alice", &["Bob"]);
+        blockchain.vote_for_delegate("David", &["Alice", "Bob", "Charlie", "David"]);
+        blockchain.vote_for_delegate("David", &["Charlie"]);
+        blockchain.withdraw_vote("Charlie");
+        blockchain.withdraw_vote("Charlie");
+        blockchain.vote_for_delegate("Charlie", &["Bob", "David"]);
+        blockchain.vote_for_delegate("Erin", &["Alice"]);
+        // The transfer is queued; Alice's vote for Bob shrinks and David's vote for Charlie
+        // grows only once a block includes it.
+        if let Err(err) = blockchain.transfer_stake("Alice", "David", 5) {
+            println!("Transfer rejected: {}", err);
+        }
+        if let Err(err) = blockchain.transfer_stake("David", "Alice", 500) {
+            println!("Transfer rejected: {}", err);
+        }
+        blockchain.tally_report();
+        // Charlie approved two delegates, so split weighting halves his stake between them.
+        blockchain.weighting = VoteWeighting::Split;
+        blockchain.recompute_tallies();
+        println!("Tallies with split weighting:");
+        blockchain.tally_report();
+        blockchain.weighting = VoteWeighting::Full;
+        blockchain.elect_delegates(3); // En çok oy alan 3 kişi seçiliyor
     }
 
     // İşlem ekleme ve blok oluşturma
@@ -181,7 +309,78 @@ fn main() {
 
     {
         let mut blockchain = blockchain.lock().unwrap();
-        blockchain.finalize_pending_transactions(); // Blok üretiliyor
+        for _ in 0..6 {
+            blockchain.finalize_pending_transactions(); // Blok üretiliyor
+        }
+        println!("Tallies after the transfer is included:");
+        blockchain.tally_report();
+    }
+
+    // A delegate producing in someone else's slot is rejected.
+    {
+        let mut blockchain = blockchain.lock().unwrap();
+        let slot = blockchain.advance_slot();
+        let producer = blockchain.producer_for_slot(slot).unwrap();
+        let intruder = blockchain.delegates.iter().find(|delegate| **delegate != producer).unwrap().clone();
+        let last_block = blockchain.chain.back().unwrap();
+        let block = Block::new(last_block.id + 1, slot, last_block.hash.clone(), vec![], intruder);
+        if let Err(err) = blockchain.append_block(block) {
+            println!("Block rejected: {}", err);
+        }
+    }
+
+    // Charlie goes offline and is replaced by standby Alice after missing two slots in a row.
+    {
+        let mut blockchain = blockchain.lock().unwrap();
+        blockchain.max_consecutive_missed = 1;
+        blockchain.set_online("Charlie", false);
+        for _ in 0..10 {
+            blockchain.finalize_pending_transactions();
+        }
+        println!("Delegates: {:?}", blockchain.delegates);
+        blockchain.reliability_report();
+    }
+
+    // Two slots pass without their blocks reaching us. A peer's fork from just below our head
+    // that fills them is longer and valid, so it is accepted. A fork sealed by Bob alone in
+    // other delegates' slots is refused, and so is one that branches off below the last
+    // irreversible block.
+    {
+        let mut blockchain = blockchain.lock().unwrap();
+        let slots = [blockchain.advance_slot(), blockchain.advance_slot()];
+        let head = blockchain.chain.back().unwrap().id;
+        println!("Last irreversible block: {} (head {})", blockchain.last_irreversible, head);
+        let branch = |blockchain: &Blockchain, fork_point: u64| -> VecDeque<Block> {
+            blockchain.chain.iter().take_while(|block| block.id <= fork_point).cloned().collect()
+        };
+
+        let mut fork = branch(&blockchain, head - 1);
+        for slot in slots {
+            let parent = fork.back().unwrap();
+            let producer = blockchain.producer_for_slot(slot).unwrap();
+            fork.push_back(Block::new(parent.id + 1, slot, parent.hash.clone(), vec![], producer));
+        }
+        match blockchain.switch_to_fork(fork) {
+            Ok(true) => println!("Switched to fork from block {}", head - 1),
+            Ok(false) => println!("Kept our chain over a fork that is not longer"),
+            Err(err) => println!("Fork refused: {}", err),
+        }
+
+        let head = blockchain.chain.back().unwrap().id;
+        println!("Last irreversible block: {} (head {})", blockchain.last_irreversible, head);
+        for fork_point in [blockchain.last_irreversible, blockchain.last_irreversible - 1] {
+            let mut fork = branch(&blockchain, fork_point);
+            while fork.len() <= blockchain.chain.len() {
+                let parent = fork.back().unwrap();
+                let block = Block::new(parent.id + 1, parent.slot + 1, parent.hash.clone(), vec![], "Bob".to_string());
+                fork.push_back(block);
+            }
+            match blockchain.switch_to_fork(fork) {
+                Ok(true) => println!("Switched to fork from block {}", fork_point),
+                Ok(false) => println!("Kept our chain over a fork that is not longer"),
+                Err(err) => println!("Fork refused: {}", err),
+            }
+        }
     }
 
     // Blockchain görüntüleme
@@ -0,0 +1,141 @@
+use super::Blockchain;
+
+#[derive(Debug, Clone, Default)]
+pub struct ProductionStats {
+    pub produced: u64,
+    pub missed: u64,
+    pub consecutive_missed: u64,
+}
+
+impl ProductionStats {
+    // Share of assigned slots the delegate actually filled, as a percentage.
+    pub fn reliability(&self) -> f64 {
+        let assigned = self.produced + self.missed;
+        if assigned == 0 {
+            return 100.0;
+        }
+        self.produced as f64 * 100.0 / assigned as f64
+    }
+}
+
+impl Blockchain {
+    pub fn set_online(&mut self, name: &str, online: bool) {
+        if online {
+            self.offline.remove(name);
+        } else {
+            self.offline.insert(name.to_string());
+        }
+    }
+
+    pub fn record_produced(&mut self, delegate: &str) {
+        let stats = self.production.entry(delegate.to_string()).or_default();
+        stats.produced += 1;
+        stats.consecutive_missed = 0;
+    }
+
+    // Counts a slot that passed without a block and replaces the delegate once it has missed
+    // more than `max_consecutive_missed` slots in a row.
+    pub fn record_missed_slot(&mut self, slot: u64, delegate: &str) {
+        let stats = self.production.entry(delegate.to_string()).or_default();
+        stats.missed += 1;
+        stats.consecutive_missed += 1;
+        println!("Slot {} missed by {}", slot, delegate);
+        if stats.consecutive_missed > self.max_consecutive_missed {
+            self.replace_delegate(delegate);
+        }
+    }
+
+    // The highest-voted node that is online, has votes, and is neither elected nor previously
+    // dropped.
+    fn next_standby(&self) -> Option<String> {
+        let mut candidates: Vec<_> = self
+            .nodes
+            .values()
+            .filter(|node| {
+                node.votes > 0
+                    && !self.delegates.contains(&node.name)
+                    && !self.dropped.contains(&node.name)
+                    && !self.offline.contains(&node.name)
+            })
+            .collect();
+        candidates.sort_by_key(|node| (std::cmp::Reverse(node.votes), node.name.clone()));
+        candidates.first().map(|node| node.name.clone())
+    }
+
+    // Drops `delegate` and promotes the next standby into its seat and its remaining slots
+    // in the current round. Slots already passed stay assigned to `delegate`; later rounds are
+    // shuffled from the updated delegate list.
+    fn replace_delegate(&mut self, delegate: &str) {
+        self.dropped.insert(delegate.to_string());
+        let Some(standby) = self.next_standby() else {
+            println!("{} keeps its seat: no standby candidate available", delegate);
+            return;
+        };
+        for seat in self.delegates.iter_mut().filter(|seat| *seat == delegate) {
+            *seat = standby.clone();
+        }
+        if let Some(round) = self.rounds.last_mut() {
+            let passed = (self.current_slot + 1).saturating_sub(round.first_slot) as usize;
+            for seat in round.producers.iter_mut().skip(passed).filter(|seat| *seat == delegate) {
+                *seat = standby.clone();
+            }
+        }
+        println!("{} dropped after missing too many slots; {} promoted from standby", delegate, standby);
+    }
+
+    pub fn reliability_report(&self) {
+        let mut names: Vec<_> = self.production.keys().collect();
+        names.sort();
+        for name in names {
+            let stats = &self.production[name];
+            println!(
+                "  {}: produced {}, missed {}, reliability {:.1}%",
+                name,
+                stats.produced,
+                stats.missed,
+                stats.reliability()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Blockchain, Node};
+
+    // Alice and Bob are elected; Charlie and David stand by with votes, Erin without.
+    fn elected() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for (name, stake) in [("Alice", 0), ("Bob", 0), ("Charlie", 0), ("David", 0), ("Erin", 0), ("Whale", 10), ("Dana", 5)] {
+            blockchain.register_node(Node::new(name, stake));
+        }
+        blockchain.vote_for_delegate("Whale", &["Alice", "Bob", "Charlie"]);
+        blockchain.vote_for_delegate("Dana", &["David"]);
+        blockchain.elect_delegates(2);
+        blockchain
+    }
+
+    #[test]
+    fn offline_and_unvoted_candidates_are_not_promoted() {
+        let mut blockchain = elected();
+        assert_eq!(blockchain.delegates, ["Alice", "Bob"]);
+        blockchain.set_online("Charlie", false);
+        assert_eq!(blockchain.next_standby().as_deref(), Some("David"));
+
+        blockchain.replace_delegate("Alice");
+        assert_eq!(blockchain.delegates, ["David", "Bob"]);
+        // Only Charlie, who is offline, and Erin, who has no votes, are left.
+        blockchain.replace_delegate("Bob");
+        assert_eq!(blockchain.delegates, ["David", "Bob"]);
+    }
+
+    #[test]
+    fn election_clears_dropped_delegates() {
+        let mut blockchain = elected();
+        blockchain.replace_delegate("Alice");
+        assert!(blockchain.dropped.contains("Alice"));
+        blockchain.elect_delegates(2);
+        assert!(blockchain.dropped.is_empty());
+        assert_eq!(blockchain.delegates, ["Alice", "Bob"]);
+    }
+}
@@ -0,0 +1,183 @@
+use super::{Block, BlockError, Blockchain};
+use sha2::{Digest, Sha256};
+
+// A round of slots starting at `first_slot`, one per producer in order.
+#[derive(Debug, Clone)]
+pub struct Round {
+    pub number: u64,
+    pub first_slot: u64,
+    pub producers: Vec<String>,
+}
+
+impl Round {
+    pub fn contains(&self, slot: u64) -> bool {
+        slot >= self.first_slot && slot < self.first_slot + self.producers.len() as u64
+    }
+}
+
+impl Blockchain {
+    // The elected delegates in a deterministic order for `round`: a Fisher-Yates shuffle driven
+    // by hashes of the schedule seed and the round number, so every node derives the same order.
+    pub fn shuffled_schedule(&self, round: u64) -> Vec<String> {
+        let mut schedule = self.delegates.clone();
+        for i in (1..schedule.len()).rev() {
+            let mut hasher = Sha256::new();
+            hasher.update(self.schedule_seed.to_le_bytes());
+            hasher.update(round.to_le_bytes());
+            hasher.update((i as u64).to_le_bytes());
+            let digest = hasher.finalize();
+            let value = u64::from_le_bytes(digest[..8].try_into().unwrap());
+            schedule.swap(i, (value % (i as u64 + 1)) as usize);
+        }
+        schedule
+    }
+
+    // The round that would follow `previous`, shuffled from the current delegates.
+    fn following_round(&self, previous: Option<&Round>) -> Round {
+        let number = previous.map_or(0, |round| round.number + 1);
+        Round {
+            number,
+            first_slot: previous.map_or(1, |round| round.first_slot + round.producers.len() as u64),
+            producers: self.shuffled_schedule(number),
+        }
+    }
+
+    // Slots are numbered from 1. Slots we have reached keep the round recorded for them, so a
+    // later replacement or election never changes who was due to produce; later slots fall in
+    // the rounds that would follow the last recorded one.
+    pub fn round_of(&self, slot: u64) -> Option<Round> {
+        if slot == 0 || self.delegates.is_empty() {
+            return None;
+        }
+        if let Some(round) = self.rounds.iter().rev().find(|round| round.first_slot <= slot) {
+            if round.contains(slot) {
+                return Some(round.clone());
+            }
+        }
+        let mut round = self.following_round(self.rounds.last());
+        if slot < round.first_slot {
+            return None;
+        }
+        while !round.contains(slot) {
+            round = self.following_round(Some(&round));
+        }
+        Some(round)
+    }
+
+    pub fn producer_for_slot(&self, slot: u64) -> Option<String> {
+        let round = self.round_of(slot)?;
+        round.producers.get((slot - round.first_slot) as usize).cloned()
+    }
+
+    // Moves to the next slot, recording a new round when the current one is over.
+    pub fn advance_slot(&mut self) -> u64 {
+        self.current_slot += 1;
+        let started = self.rounds.last().is_some_and(|round| round.contains(self.current_slot));
+        if !started && !self.delegates.is_empty() {
+            let mut round = self.following_round(self.rounds.last());
+            round.first_slot = self.current_slot;
+            println!("Round {} schedule: {:?}", round.number, round.producers);
+            self.rounds.push(round);
+        }
+        self.current_slot
+    }
+
+    // Ends the current round with the current slot, so the next slot starts a round over the
+    // newly elected delegates.
+    pub fn end_round(&mut self) {
+        let current_slot = self.current_slot;
+        if let Some(round) = self.rounds.last_mut() {
+            round.producers.truncate((current_slot + 1).saturating_sub(round.first_slot) as usize);
+        }
+    }
+
+    // Accepts only blocks that extend our head, for a later slot, from that slot's delegate.
+    pub fn append_block(&mut self, block: Block) -> Result<(), BlockError> {
+        let parent = self.chain.back().ok_or(BlockError::UnknownParent(block.id))?;
+        if block.previous_hash != parent.hash || block.id != parent.id + 1 {
+            return Err(BlockError::UnknownParent(block.id));
+        }
+        if block.hash != block.calculate_hash() {
+            return Err(BlockError::InvalidHash(block.id));
+        }
+        if block.slot <= parent.slot || block.slot > self.current_slot {
+            return Err(BlockError::InvalidSlot(block.slot));
+        }
+        let expected = self.producer_for_slot(block.slot).unwrap_or_default();
+        if block.validator != expected {
+            return Err(BlockError::OutOfSlot {
+                slot: block.slot,
+                producer: block.validator.clone(),
+                expected,
+            });
+        }
+        self.apply_transfers(&block)?;
+        self.record_produced(&block.validator);
+        self.confirm_ancestors(&block);
+        self.chain.push_back(block);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Block, Blockchain, Node};
+    use std::collections::VecDeque;
+
+    // Alice, Bob and Charlie are elected; David stands by.
+    fn elected() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for (name, stake) in [("Alice", 0), ("Bob", 0), ("Charlie", 0), ("David", 0), ("Whale", 10), ("Dana", 5)] {
+            blockchain.register_node(Node::new(name, stake));
+        }
+        blockchain.vote_for_delegate("Whale", &["Alice", "Bob", "Charlie"]);
+        blockchain.vote_for_delegate("Dana", &["David"]);
+        blockchain.elect_delegates(3);
+        blockchain
+    }
+
+    #[test]
+    fn replaced_delegate_keeps_its_past_slots() {
+        let mut blockchain = elected();
+        blockchain.max_consecutive_missed = 0;
+        for _ in 0..3 {
+            blockchain.finalize_pending_transactions();
+        }
+        let victim = blockchain.producer_for_slot(4).unwrap();
+        blockchain.set_online(&victim, false);
+        blockchain.finalize_pending_transactions();
+        assert!(!blockchain.delegates.contains(&victim));
+        assert_eq!(blockchain.producer_for_slot(4), Some(victim.clone()));
+
+        // A peer did receive the victim's block for slot 4; its branch still validates.
+        let mut fork: VecDeque<Block> = blockchain
+            .chain
+            .iter()
+            .take_while(|block| block.id <= blockchain.last_irreversible)
+            .cloned()
+            .collect();
+        for slot in fork.back().unwrap().slot + 1..=4 {
+            let parent = fork.back().unwrap();
+            let producer = blockchain.producer_for_slot(slot).unwrap();
+            fork.push_back(Block::new(parent.id + 1, slot, parent.hash.clone(), vec![], producer));
+        }
+        assert!(blockchain.switch_to_fork(fork).unwrap());
+        assert_eq!(blockchain.chain.back().unwrap().validator, victim);
+    }
+
+    #[test]
+    fn election_starts_a_new_round_without_changing_past_slots() {
+        let mut blockchain = elected();
+        for _ in 0..4 {
+            blockchain.finalize_pending_transactions();
+        }
+        let past: Vec<_> = (1..=4).map(|slot| blockchain.producer_for_slot(slot)).collect();
+
+        blockchain.elect_delegates(2);
+        assert_eq!((1..=4).map(|slot| blockchain.producer_for_slot(slot)).collect::<Vec<_>>(), past);
+        blockchain.finalize_pending_transactions();
+        let round = blockchain.round_of(5).unwrap();
+        assert_eq!(round.first_slot, 5);
+        assert_eq!(round.producers.len(), 2);
+    }
+}
@@ -0,0 +1,229 @@
+use super::{Block, BlockError, Blockchain, Node};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct StakeTransfer {
+    pub sender: String,
+    pub receiver: String,
+    pub amount: u64,
+}
+
+impl fmt::Display for StakeTransfer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {}: {} stake", self.sender, self.receiver, self.amount)
+    }
+}
+
+#[derive(Debug)]
+pub enum TransferError {
+    UnknownSender(String),
+    InsufficientStake { sender: String, balance: u64, amount: u64 },
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransferError::UnknownSender(sender) => write!(f, "{} has no stake to transfer", sender),
+            TransferError::InsufficientStake { sender, balance, amount } => {
+                write!(f, "{} cannot transfer {} with a balance of {}", sender, amount, balance)
+            }
+        }
+    }
+}
+
+impl Blockchain {
+    // Queues a stake transfer for the next block. The sender's balance must cover it on top of
+    // the transfers it already has pending; balances and tallies change only when a block
+    // carrying the transfer is appended.
+    pub fn transfer_stake(&mut self, sender: &str, receiver: &str, amount: u64) -> Result<(), TransferError> {
+        let balance = self
+            .nodes
+            .get(sender)
+            .ok_or_else(|| TransferError::UnknownSender(sender.to_string()))?
+            .stake;
+        let pending: u64 = self
+            .pending_transfers
+            .iter()
+            .filter(|transfer| transfer.sender == sender)
+            .map(|transfer| transfer.amount)
+            .sum();
+        let available = balance.saturating_sub(pending);
+        if available < amount {
+            return Err(TransferError::InsufficientStake {
+                sender: sender.to_string(),
+                balance: available,
+                amount,
+            });
+        }
+        self.pending_transfers.push(StakeTransfer {
+            sender: sender.to_string(),
+            receiver: receiver.to_string(),
+            amount,
+        });
+        Ok(())
+    }
+
+    // Applies the transfers in a block being appended, all or none. Tallies are rebuilt from the
+    // new balances, so the moved coins stop backing the sender's delegates and back the
+    // receiver's instead.
+    pub fn apply_transfers(&mut self, block: &Block) -> Result<(), BlockError> {
+        let mut balances: HashMap<String, u64> = HashMap::new();
+        for transfer in &block.transfers {
+            let sender = balances
+                .entry(transfer.sender.clone())
+                .or_insert_with(|| self.nodes.get(&transfer.sender).map_or(0, |node| node.stake));
+            if *sender < transfer.amount {
+                return Err(BlockError::InvalidTransfer(block.id));
+            }
+            *sender -= transfer.amount;
+            *balances
+                .entry(transfer.receiver.clone())
+                .or_insert_with(|| self.nodes.get(&transfer.receiver).map_or(0, |node| node.stake)) += transfer.amount;
+        }
+        if balances.is_empty() {
+            return Ok(());
+        }
+        for (name, stake) in balances {
+            self.nodes.entry(name.clone()).or_insert_with(|| Node::new(&name, 0)).stake = stake;
+        }
+        self.recompute_tallies();
+        Ok(())
+    }
+
+    // Drops queued transfers their senders can no longer cover, e.g. after a fork switch undid
+    // the transfer that funded them; otherwise every block carrying the queue would be rejected.
+    pub fn revalidate_pending_transfers(&mut self) {
+        let mut balances: HashMap<String, u64> = HashMap::new();
+        let nodes = &self.nodes;
+        let stake = |name: &str| nodes.get(name).map_or(0, |node| node.stake);
+        self.pending_transfers.retain(|transfer| {
+            let sender = balances.entry(transfer.sender.clone()).or_insert_with(|| stake(&transfer.sender));
+            if *sender < transfer.amount {
+                println!("Dropped pending transfer {}: balance is now {}", transfer, sender);
+                return false;
+            }
+            *sender -= transfer.amount;
+            *balances.entry(transfer.receiver.clone()).or_insert_with(|| stake(&transfer.receiver)) += transfer.amount;
+            true
+        });
+    }
+
+    // Undoes the transfers of a block dropped by a fork switch.
+    pub fn revert_transfers(&mut self, block: &Block) {
+        for transfer in block.transfers.iter().rev() {
+            if let Some(receiver) = self.nodes.get_mut(&transfer.receiver) {
+                receiver.stake -= transfer.amount;
+            }
+            if let Some(sender) = self.nodes.get_mut(&transfer.sender) {
+                sender.stake += transfer.amount;
+            }
+        }
+        self.recompute_tallies();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voting::VoteWeighting;
+
+    // Bob is the only delegate, so he seals every block.
+    fn blockchain(weighting: VoteWeighting) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        blockchain.weighting = weighting;
+        for node in [Node::new("Bob", 0), Node::new("Charlie", 0), Node::new("Alice", 50), Node::new("Mallory", 0)] {
+            blockchain.register_node(node);
+        }
+        blockchain.vote_for_delegate("Alice", &["Bob"]);
+        blockchain.elect_delegates(1);
+        blockchain
+    }
+
+    fn votes(blockchain: &Blockchain, name: &str) -> u64 {
+        blockchain.nodes[name].votes
+    }
+
+    fn total_stake(blockchain: &Blockchain) -> u64 {
+        blockchain.nodes.values().map(|node| node.stake).sum()
+    }
+
+    #[test]
+    fn transfer_applies_only_when_its_block_is_appended() {
+        let mut blockchain = blockchain(VoteWeighting::Full);
+        blockchain.transfer_stake("Alice", "Mallory", 20).unwrap();
+        assert_eq!(blockchain.nodes["Alice"].stake, 50);
+        assert_eq!(votes(&blockchain, "Bob"), 50);
+
+        blockchain.finalize_pending_transactions();
+        assert_eq!(blockchain.chain.back().unwrap().transfers.len(), 1);
+        assert_eq!(blockchain.nodes["Alice"].stake, 30);
+        assert_eq!(blockchain.nodes["Mallory"].stake, 20);
+        assert_eq!(votes(&blockchain, "Bob"), 30);
+    }
+
+    #[test]
+    fn moved_stake_is_not_counted_twice() {
+        let mut blockchain = blockchain(VoteWeighting::Full);
+        blockchain.transfer_stake("Alice", "Mallory", 50).unwrap();
+        // The pending transfer already spends Alice's balance.
+        assert!(matches!(
+            blockchain.transfer_stake("Alice", "Mallory", 50),
+            Err(TransferError::InsufficientStake { balance: 0, .. })
+        ));
+        blockchain.finalize_pending_transactions();
+        blockchain.vote_for_delegate("Mallory", &["Charlie"]);
+        blockchain.elect_delegates(1);
+
+        assert_eq!((votes(&blockchain, "Bob"), votes(&blockchain, "Charlie")), (0, 50));
+        assert_eq!(blockchain.delegates, ["Charlie"]);
+        let total_votes: u64 = blockchain.nodes.values().map(|node| node.votes).sum();
+        assert!(total_votes <= total_stake(&blockchain));
+    }
+
+    // With full weighting one account's stake backs each of its approvals in full, so tallies
+    // may add up to more than the total stake. Moving the stake must still leave each delegate
+    // backed by it once.
+    #[test]
+    fn multi_approval_stake_backs_each_delegate_once() {
+        let mut blockchain = blockchain(VoteWeighting::Full);
+        blockchain.vote_for_delegate("Alice", &["Bob", "Charlie"]);
+        blockchain.transfer_stake("Alice", "Mallory", 50).unwrap();
+        blockchain.finalize_pending_transactions();
+        blockchain.vote_for_delegate("Mallory", &["Bob", "Charlie"]);
+
+        for delegate in ["Bob", "Charlie"] {
+            assert_eq!(votes(&blockchain, delegate), total_stake(&blockchain));
+        }
+    }
+
+    #[test]
+    fn overdrawn_queue_rejects_new_transfers_without_panicking() {
+        let mut blockchain = blockchain(VoteWeighting::Full);
+        blockchain.transfer_stake("Alice", "Mallory", 50).unwrap();
+        // As if a fork switch undid stake Alice had received.
+        blockchain.nodes.get_mut("Alice").unwrap().stake = 30;
+        assert!(matches!(
+            blockchain.transfer_stake("Alice", "Mallory", 1),
+            Err(TransferError::InsufficientStake { balance: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn block_overspending_a_sender_is_rejected() {
+        let mut blockchain = blockchain(VoteWeighting::Full);
+        let slot = blockchain.advance_slot();
+        let parent = blockchain.chain.back().unwrap();
+        let mut block = Block::new(parent.id + 1, slot, parent.hash.clone(), vec![], "Bob".to_string());
+        block.transfers = vec![StakeTransfer {
+            sender: "Alice".to_string(),
+            receiver: "Mallory".to_string(),
+            amount: 60,
+        }];
+        block.finalize_block();
+
+        assert!(matches!(blockchain.append_block(block), Err(BlockError::InvalidTransfer(1))));
+        assert_eq!(blockchain.nodes["Alice"].stake, 50);
+        assert_eq!(blockchain.chain.len(), 1);
+    }
+}
@@ -0,0 +1,102 @@
+use super::Blockchain;
+
+// How a voter's stake is counted when they approve several delegates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoteWeighting {
+    // Every approved delegate receives the voter's full stake.
+    Full,
+    // The stake is divided evenly between the approved delegates.
+    Split,
+}
+
+impl Blockchain {
+    // Takes back all of the voter's current approvals, if any.
+    pub fn withdraw_vote(&mut self, voter: &str) {
+        match self.vote_records.remove(voter) {
+            Some(delegates) => {
+                self.recompute_tallies();
+                println!("{} withdrew their votes for {:?}", voter, delegates);
+            }
+            None => println!("{} has no vote to withdraw.", voter),
+        }
+    }
+
+    // Rebuilds every delegate's tally from the current vote records, so a tally never holds
+    // more than the stake of the voters currently backing the delegate.
+    pub fn recompute_tallies(&mut self) {
+        for node in self.nodes.values_mut() {
+            node.votes = 0;
+        }
+        for (voter, delegates) in &self.vote_records {
+            let stake = self.nodes.get(voter).map_or(0, |node| node.stake);
+            for (index, delegate) in delegates.iter().enumerate() {
+                let weight = match self.weighting {
+                    VoteWeighting::Full => stake,
+                    // The remainder goes to the first approvals so no stake is lost to rounding.
+                    VoteWeighting::Split => {
+                        let count = delegates.len() as u64;
+                        stake / count + u64::from((index as u64) < stake % count)
+                    }
+                };
+                if let Some(delegate_node) = self.nodes.get_mut(delegate) {
+                    delegate_node.votes += weight;
+                }
+            }
+        }
+    }
+
+    pub fn tally_report(&self) {
+        let mut nodes: Vec<_> = self.nodes.values().collect();
+        nodes.sort_by_key(|node| (std::cmp::Reverse(node.votes), node.name.clone()));
+        for node in nodes {
+            println!("  {}: {} votes", node.name, node.votes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    // A single large holder backs one candidate while three smaller holders back a slate of two.
+    fn elect_with(weighting: VoteWeighting) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        blockchain.weighting = weighting;
+        for node in [
+            Node::new("Alice", 0),
+            Node::new("Bob", 0),
+            Node::new("Charlie", 0),
+            Node::new("Whale", 100),
+            Node::new("Dana", 40),
+            Node::new("Erin", 40),
+            Node::new("Frank", 40),
+        ] {
+            blockchain.register_node(node);
+        }
+        blockchain.vote_for_delegate("Whale", &["Alice"]);
+        for voter in ["Dana", "Erin", "Frank"] {
+            blockchain.vote_for_delegate(voter, &["Bob", "Charlie"]);
+        }
+        blockchain.elect_delegates(2);
+        blockchain
+    }
+
+    fn tallies(blockchain: &Blockchain) -> [u64; 3] {
+        ["Alice", "Bob", "Charlie"].map(|name| blockchain.nodes[name].votes)
+    }
+
+    #[test]
+    fn full_weighting_elects_the_broad_slate() {
+        let blockchain = elect_with(VoteWeighting::Full);
+        assert_eq!(tallies(&blockchain), [100, 120, 120]);
+        assert_eq!(blockchain.delegates, ["Bob", "Charlie"]);
+    }
+
+    #[test]
+    fn split_weighting_seats_the_large_holder() {
+        let blockchain = elect_with(VoteWeighting::Split);
+        assert_eq!(tallies(&blockchain), [100, 60, 60]);
+        assert_eq!(blockchain.delegates, ["Alice", "Bob"]);
+    }
+}
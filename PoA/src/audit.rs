@@ -0,0 +1,90 @@
+use super::{Block, BlockError, Blockchain};
+use std::collections::BTreeSet;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthorityChange {
+    Registered,
+    Approved,
+    Removed,
+    KeyRotated,
+    KeyRevoked,
+    Suspended,
+    Reinstated,
+}
+
+impl fmt::Display for AuthorityChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AuthorityChange::Registered => "registered",
+            AuthorityChange::Approved => "approved",
+            AuthorityChange::Removed => "removed",
+            AuthorityChange::KeyRotated => "rotated its key",
+            AuthorityChange::KeyRevoked => "had its key revoked",
+            AuthorityChange::Suspended => "suspended",
+            AuthorityChange::Reinstated => "reinstated",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A change to the authority set, effective from `height`, and who approved it. An empty
+// `approved_by` means the change came from the genesis configuration or the liveness monitor.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub height: u64,
+    pub authority: String,
+    pub change: AuthorityChange,
+    pub approved_by: Vec<String>,
+}
+
+impl Blockchain {
+    pub fn record_change(&mut self, height: u64, authority: &str, change: AuthorityChange, approved_by: Vec<String>) {
+        self.audit_log.push(AuditEntry {
+            height,
+            authority: authority.to_string(),
+            change,
+            approved_by,
+        });
+    }
+
+    // Replays the audit log to find the authorities allowed to seal at `height`.
+    pub fn authorities_at(&self, height: u64) -> Vec<String> {
+        let mut valid = BTreeSet::new();
+        for entry in self.audit_log.iter().filter(|entry| entry.height <= height) {
+            match entry.change {
+                AuthorityChange::Registered | AuthorityChange::Approved | AuthorityChange::Reinstated => {
+                    valid.insert(entry.authority.clone());
+                }
+                AuthorityChange::Removed | AuthorityChange::KeyRevoked | AuthorityChange::Suspended => {
+                    valid.remove(&entry.authority);
+                }
+                AuthorityChange::KeyRotated => {}
+            }
+        }
+        valid.into_iter().collect()
+    }
+
+    // Re-checks an old block against the authority set and keys in force when it was sealed,
+    // rather than against today's signers.
+    pub fn revalidate_block(&self, block: &Block) -> Result<(), BlockError> {
+        if block.hash != block.calculate_hash() {
+            return Err(BlockError::InvalidHash(block.id));
+        }
+        if !self.authorities_at(block.id).contains(&block.validator) {
+            return Err(BlockError::UnauthorizedSigner(block.validator.clone()));
+        }
+        self.verify_seal(block)
+    }
+
+    pub fn audit_report(&self) {
+        for entry in &self.audit_log {
+            let approved_by = if entry.approved_by.is_empty() {
+                String::new()
+            } else {
+                format!(" (approved by {})", entry.approved_by.join(", "))
+            };
+            println!("  height {}: {} {}{}", entry.height, entry.authority, entry.change, approved_by);
+        }
+    }
+}
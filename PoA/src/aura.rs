@@ -0,0 +1,81 @@
+use super::{Block, BlockError, Blockchain};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SealingMode {
+    Clique,
+    Aura,
+    Ibft,
+    Reputation,
+}
+
+// Simulated wall clock for Aura. Time only moves when `advance` is called, so step schedules are
+// reproducible from run to run.
+#[derive(Debug)]
+pub struct StepClock {
+    pub step_duration: Duration,
+    elapsed: Duration,
+}
+
+impl StepClock {
+    pub fn new(step_duration: Duration) -> Self {
+        StepClock {
+            step_duration,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn advance(&mut self, by: Duration) {
+        self.elapsed += by;
+    }
+
+    pub fn current_step(&self) -> u64 {
+        (self.elapsed.as_millis() / self.step_duration.as_millis()) as u64
+    }
+}
+
+impl Blockchain {
+    // The only authority allowed to author a block in `step`.
+    pub fn step_author(&self, step: u64) -> Option<String> {
+        let signers = self.signers();
+        if signers.is_empty() {
+            return None;
+        }
+        Some(signers[(step % signers.len() as u64) as usize].clone())
+    }
+
+    // Moves the clock to the next step and returns it with its author, or None if the author is
+    // offline, in which case the step passes without a block.
+    pub fn next_aura_step(&mut self) -> Option<(String, u64)> {
+        self.step_clock.advance(self.step_clock.step_duration);
+        let step = self.step_clock.current_step();
+        let author = self.step_author(step)?;
+        if self.offline.contains(&author) {
+            println!("Step {} skipped: {} is offline", step, author);
+            return None;
+        }
+        Some((author, step))
+    }
+
+    // Steps must increase along the chain, may not lie in the future, and belong to the sealer.
+    pub fn validate_step(&self, parent: &Block, block: &Block) -> Result<(), BlockError> {
+        let current = self.step_clock.current_step();
+        if block.step > current {
+            return Err(BlockError::FutureStep { step: block.step, current });
+        }
+        if parent.id > 0 && block.step <= parent.step {
+            return Err(BlockError::StepNotIncreasing(block.step));
+        }
+        let expected = self.step_author(block.step).unwrap_or_default();
+        if block.validator != expected {
+            return Err(BlockError::WrongStepAuthor { step: block.step, expected });
+        }
+        Ok(())
+    }
+
+    pub fn step_report(&self) {
+        for block in self.chain.iter().skip(1) {
+            println!("  step {}: block {} by {}", block.step, block.id, block.validator);
+        }
+    }
+}
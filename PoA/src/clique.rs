@@ -0,0 +1,405 @@
+use super::{AuditEntry, Authority, AuthorityChange, Block, BlockError, Blockchain, Duty, Role, SealingMode, Vote};
+use rand::Rng;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+pub const DIFF_IN_TURN: u64 = 2;
+pub const DIFF_NO_TURN: u64 = 1;
+const WIGGLE_TIME: Duration = Duration::from_millis(500);
+
+// Sum of block difficulties; every in-turn block counts double.
+pub fn total_difficulty(chain: &VecDeque<Block>) -> u64 {
+    chain.iter().map(|block| block.difficulty).sum()
+}
+
+// Everything blocks change about the authority set and the sender allow-list. It is saved
+// before each block is appended so that a fork can be replayed from its fork point.
+#[derive(Debug, Clone)]
+pub struct AuthorityState {
+    authorities: HashMap<String, Authority>,
+    votes: Vec<Vote>,
+    duties: Vec<Duty>,
+    suspensions: HashMap<String, u64>,
+    liveness_reset: HashMap<String, u64>,
+    audit_log: Vec<AuditEntry>,
+    revocation_votes: HashMap<String, HashSet<String>>,
+    permissions: HashMap<String, HashSet<Role>>,
+    permission_votes: HashMap<(String, Role, bool), HashSet<String>>,
+    nonces: HashMap<String, u64>,
+    schedule_credit: HashMap<String, f64>,
+    reputation_schedule: Vec<String>,
+}
+
+impl Blockchain {
+    pub fn authority_state(&self) -> AuthorityState {
+        AuthorityState {
+            authorities: self.authorities.clone(),
+            votes: self.votes.clone(),
+            duties: self.duties.clone(),
+            suspensions: self.suspensions.clone(),
+            liveness_reset: self.liveness_reset.clone(),
+            audit_log: self.audit_log.clone(),
+            revocation_votes: self.revocation_votes.clone(),
+            permissions: self.permissions.clone(),
+            permission_votes: self.permission_votes.clone(),
+            nonces: self.nonces.clone(),
+            schedule_credit: self.schedule_credit.clone(),
+            reputation_schedule: self.reputation_schedule.clone(),
+        }
+    }
+
+    fn restore_authority_state(&mut self, state: AuthorityState) {
+        self.authorities = state.authorities;
+        self.votes = state.votes;
+        self.duties = state.duties;
+        self.suspensions = state.suspensions;
+        self.liveness_reset = state.liveness_reset;
+        self.audit_log = state.audit_log;
+        self.revocation_votes = state.revocation_votes;
+        self.permissions = state.permissions;
+        self.permission_votes = state.permission_votes;
+        self.nonces = state.nonces;
+        self.schedule_credit = state.schedule_credit;
+        self.reputation_schedule = state.reputation_schedule;
+    }
+
+    // Approved authorities in sorted order, which fixes the in-turn rotation.
+    pub fn signers(&self) -> Vec<String> {
+        let mut signers: Vec<_> = self
+            .authorities
+            .values()
+            .filter(|authority| authority.approved && !self.suspensions.contains_key(&authority.name))
+            .map(|authority| authority.name.clone())
+            .collect();
+        signers.sort();
+        signers
+    }
+
+    pub fn in_turn_signer(&self, height: u64) -> Option<String> {
+        let signers = self.signers();
+        if signers.is_empty() {
+            return None;
+        }
+        Some(signers[(height % signers.len() as u64) as usize].clone())
+    }
+
+    // Aura, IBFT and reputation-scheduled blocks are always authored in turn for their slot.
+    pub fn expected_difficulty(&self, height: u64, signer: &str) -> u64 {
+        if self.sealing_mode != SealingMode::Clique || self.in_turn_signer(height).as_deref() == Some(signer) {
+            DIFF_IN_TURN
+        } else {
+            DIFF_NO_TURN
+        }
+    }
+
+    // A signer may seal only one of any floor(n/2)+1 consecutive blocks.
+    fn recently_signed(&self, chain: &VecDeque<Block>, height: u64, signer: &str) -> bool {
+        let limit = (self.signers().len() / 2 + 1) as u64;
+        chain
+            .iter()
+            .rev()
+            .take_while(|block| block.id + limit > height)
+            .any(|block| block.id > 0 && block.validator == signer)
+    }
+
+    // The in-turn signer seals right away. If it is offline or has signed too recently, every
+    // other eligible signer waits a random delay and the fastest one seals out of turn.
+    pub fn select_validator(&self) -> Option<(String, Duration)> {
+        let height = self.chain.back().map_or(0, |block| block.id + 1);
+        let signers = self.signers();
+        let eligible: Vec<_> = signers
+            .iter()
+            .filter(|signer| !self.offline.contains(*signer) && !self.recently_signed(&self.chain, height, signer))
+            .collect();
+
+        if let Some(in_turn) = self.in_turn_signer(height) {
+            if eligible.contains(&&in_turn) {
+                return Some((in_turn, Duration::ZERO));
+            }
+        }
+
+        let mut rng = rand::thread_rng();
+        let max_delay = WIGGLE_TIME * (signers.len() / 2 + 1) as u32;
+        eligible
+            .into_iter()
+            .map(|signer| (signer.clone(), rng.gen_range(Duration::ZERO..max_delay)))
+            .min_by_key(|(_, delay)| *delay)
+    }
+
+    // Checks `block` as the next block on top of `parent_chain`.
+    pub fn validate_block(&self, parent_chain: &VecDeque<Block>, block: &Block) -> Result<(), BlockError> {
+        let parent = parent_chain.back().ok_or(BlockError::UnknownParent(block.id))?;
+        if block.previous_hash != parent.hash || block.id != parent.id + 1 {
+            return Err(BlockError::UnknownParent(block.id));
+        }
+        if block.hash != block.calculate_hash() {
+            return Err(BlockError::InvalidHash(block.id));
+        }
+        if !self.signers().contains(&block.validator) {
+            return Err(BlockError::UnauthorizedSigner(block.validator.clone()));
+        }
+        self.verify_seal(block)?;
+        match self.sealing_mode {
+            SealingMode::Clique => {
+                if self.recently_signed(parent_chain, block.id, &block.validator) {
+                    return Err(BlockError::RecentlySigned(block.validator.clone()));
+                }
+            }
+            SealingMode::Aura => self.validate_step(parent, block)?,
+            SealingMode::Ibft => self.validate_proposer(block)?,
+            SealingMode::Reputation => self.validate_slot(parent, block)?,
+        }
+        let expected = self.expected_difficulty(block.id, &block.validator);
+        if block.difficulty != expected {
+            return Err(BlockError::WrongDifficulty {
+                expected,
+                found: block.difficulty,
+            });
+        }
+        self.validate_epoch_fields(block)?;
+        self.check_block_transactions(block)?;
+        self.validate_key_changes(block)
+    }
+
+    // Switches to `fork` if it shares our genesis, is valid, and has a higher total difficulty,
+    // i.e. more in-turn blocks, regardless of its length. The fork is replayed block by block
+    // from the authority state saved at the fork point, so each block is checked against the
+    // signers of its own height and the discarded branch's votes and duties are undone.
+    pub fn consider_fork(&mut self, fork: VecDeque<Block>) -> Result<bool, BlockError> {
+        let shared = self
+            .chain
+            .iter()
+            .zip(fork.iter())
+            .take_while(|(ours, theirs)| ours.hash == theirs.hash)
+            .count();
+        if shared == 0 {
+            return Err(BlockError::UnknownParent(fork.front().map_or(0, |block| block.id)));
+        }
+        // Blocks committed by an IBFT quorum are final: only forks extending our head are
+        // considered, and their blocks must carry a quorum of commit seals.
+        if self.sealing_mode == SealingMode::Ibft && shared < self.chain.len() {
+            return Ok(false);
+        }
+        let fork_point = shared as u64 - 1;
+
+        let saved_chain = self.chain.clone();
+        let saved_state = self.authority_state();
+        let saved_snapshots = self.pre_block_states.clone();
+        // Snapshots are kept back to the last epoch checkpoint; a fork that only extends our
+        // head replays from the current state.
+        let head = self.chain.back().map_or(0, |block| block.id);
+        let replay_from = match self.pre_block_states.get(&(fork_point + 1)) {
+            Some(state) => state.clone(),
+            None if fork_point == head => saved_state.clone(),
+            None => return Err(BlockError::ForkBelowCheckpoint(fork_point)),
+        };
+        // Registrations happen outside blocks and cannot be replayed.
+        let registered = saved_state.audit_log[replay_from.audit_log.len()..]
+            .iter()
+            .any(|entry| entry.change == AuthorityChange::Registered);
+        if registered {
+            return Err(BlockError::ForkCrossesAuthorityChange(fork_point));
+        }
+
+        self.restore_authority_state(replay_from);
+        self.chain.truncate(shared);
+        self.pre_block_states.split_off(&(shared as u64));
+        let replayed = fork.into_iter().skip(shared).try_for_each(|block| self.append_block(block));
+        if replayed.is_ok() && total_difficulty(&self.chain) > total_difficulty(&saved_chain) {
+            return Ok(true);
+        }
+
+        self.chain = saved_chain;
+        self.restore_authority_state(saved_state);
+        self.pre_block_states = saved_snapshots;
+        replayed.map(|_| false)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::voting::Proposal;
+
+    pub(crate) fn blockchain_with(names: &[&str]) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for name in names {
+            blockchain.register_authority(Authority::new(name));
+        }
+        blockchain
+    }
+
+    // A node with the same authorities and genesis block as `leader` that has seen nothing since.
+    pub(crate) fn follower(leader: &Blockchain) -> Blockchain {
+        let mut follower = Blockchain::new();
+        follower.sealing_mode = leader.sealing_mode;
+        let mut names: Vec<_> = leader.authorities.keys().collect();
+        names.sort();
+        for name in names {
+            follower.register_authority(leader.authorities[name].clone());
+        }
+        follower.chain = VecDeque::from([leader.chain[0].clone()]);
+        follower
+    }
+
+    fn blockchain() -> Blockchain {
+        blockchain_with(&["Alice", "Bob", "Carol"])
+    }
+
+    pub(crate) fn sealed_block(blockchain: &Blockchain, parent: &Block, signer: &str, vote: Option<Proposal>) -> Block {
+        let height = parent.id + 1;
+        let mut block = Block::new(
+            height,
+            parent.hash.clone(),
+            vec![],
+            signer.to_string(),
+            blockchain.expected_difficulty(height, signer),
+        );
+        block.vote = vote;
+        block.finalize_block();
+        block.seal(&blockchain.authorities[signer].keypair);
+        block
+    }
+
+    // Appends in-turn blocks carrying the given votes on top of the head.
+    pub(crate) fn seal_votes(blockchain: &mut Blockchain, votes: Vec<Option<Proposal>>) {
+        for vote in votes {
+            let parent = blockchain.chain.back().unwrap().clone();
+            let signer = blockchain.in_turn_signer(parent.id + 1).unwrap();
+            let block = sealed_block(blockchain, &parent, &signer, vote);
+            blockchain.append_block(block).unwrap();
+        }
+    }
+
+    // Builds the in-turn block on top of the head the way our own node would and appends it.
+    pub(crate) fn seal_next(blockchain: &mut Blockchain) {
+        let height = blockchain.chain.back().unwrap().id + 1;
+        let signer = blockchain.in_turn_signer(height).unwrap();
+        let block = blockchain.build_block(&signer, 0, 0);
+        blockchain.append_block(block).unwrap();
+    }
+
+    #[test]
+    fn adopted_fork_undoes_votes_of_discarded_branch() {
+        let mut blockchain = blockchain();
+        let genesis = blockchain.chain[0].clone();
+        let vote = Proposal {
+            target: "Dave".to_string(),
+            authorize: true,
+        };
+        let ours = sealed_block(&blockchain, &genesis, "Bob", Some(vote));
+        blockchain.append_block(ours).unwrap();
+        assert_eq!(blockchain.votes.len(), 1);
+
+        let first = sealed_block(&blockchain, &genesis, "Bob", None);
+        let second = sealed_block(&blockchain, &first, "Carol", None);
+        let fork = VecDeque::from([genesis, first, second]);
+        assert!(blockchain.consider_fork(fork).unwrap());
+        assert_eq!(blockchain.chain.len(), 3);
+        assert!(blockchain.votes.is_empty());
+        assert_eq!(blockchain.duties.len(), 2);
+    }
+
+    #[test]
+    fn extending_adopted_fork_does_not_restore_discarded_votes() {
+        let mut blockchain = blockchain();
+        let genesis = blockchain.chain[0].clone();
+        let vote = Proposal {
+            target: "Dave".to_string(),
+            authorize: true,
+        };
+        // Five out-of-turn blocks, the first carrying Alice's vote.
+        let mut parent = genesis.clone();
+        for (signer, vote) in [("Alice", Some(vote)), ("Bob", None), ("Carol", None), ("Alice", None), ("Bob", None)] {
+            let block = sealed_block(&blockchain, &parent, signer, vote);
+            blockchain.append_block(block.clone()).unwrap();
+            parent = block;
+        }
+        assert_eq!(blockchain.votes.len(), 1);
+
+        // Three in-turn blocks outweigh them.
+        let mut fork = VecDeque::from([genesis]);
+        for signer in ["Bob", "Carol", "Alice"] {
+            let block = sealed_block(&blockchain, fork.back().unwrap(), signer, None);
+            fork.push_back(block);
+        }
+        assert!(blockchain.consider_fork(fork.clone()).unwrap());
+        assert!(blockchain.votes.is_empty());
+
+        let next = sealed_block(&blockchain, fork.back().unwrap(), "Bob", None);
+        fork.push_back(next);
+        assert!(blockchain.consider_fork(fork).unwrap());
+        assert_eq!(blockchain.chain.len(), 5);
+        assert!(blockchain.votes.is_empty());
+    }
+
+    #[test]
+    fn snapshots_are_kept_back_to_the_last_checkpoint() {
+        let mut blockchain = blockchain();
+        blockchain.epoch_length = 3;
+        seal_votes(&mut blockchain, vec![None, None]);
+        let parent = blockchain.chain.back().unwrap().clone();
+        let mut epoch_block = sealed_block(&blockchain, &parent, "Alice", None);
+        epoch_block.checkpoint = Some(blockchain.signers());
+        epoch_block.finalize_block();
+        epoch_block.seal(&blockchain.authorities["Alice"].keypair);
+        blockchain.append_block(epoch_block).unwrap();
+        seal_votes(&mut blockchain, vec![None]);
+        assert_eq!(blockchain.pre_block_states.keys().copied().collect::<Vec<_>>(), [3, 4]);
+
+        let genesis = blockchain.chain[0].clone();
+        let other = sealed_block(&blockchain, &genesis, "Bob", None);
+        assert!(matches!(
+            blockchain.consider_fork(VecDeque::from([genesis, other])),
+            Err(BlockError::ForkBelowCheckpoint(0))
+        ));
+    }
+
+    #[test]
+    fn rejected_fork_leaves_state_untouched() {
+        let mut blockchain = blockchain();
+        let genesis = blockchain.chain[0].clone();
+        let first = sealed_block(&blockchain, &genesis, "Bob", None);
+        let second = sealed_block(&blockchain, &first, "Carol", None);
+        blockchain.append_block(first).unwrap();
+        blockchain.append_block(second).unwrap();
+
+        // Out-of-turn blocks only: lower total difficulty.
+        let other = sealed_block(&blockchain, &genesis, "Alice", None);
+        let fork = VecDeque::from([genesis, other]);
+        assert!(!blockchain.consider_fork(fork).unwrap());
+        assert_eq!(blockchain.chain.len(), 3);
+        assert_eq!(blockchain.duties.len(), 2);
+    }
+
+    #[test]
+    fn adopted_fork_undoes_key_rotation_of_discarded_branch() {
+        let mut blockchain = blockchain();
+        let genesis = blockchain.chain[0].clone();
+        let old_key = blockchain.authorities["Alice"].keypair.verifying_key();
+        let (rotation, _) = blockchain.authorities["Alice"].rotate_key(2);
+        blockchain.submit_key_rotation(rotation).unwrap();
+        seal_next(&mut blockchain);
+        assert_ne!(blockchain.authorities["Alice"].key_at(2), Some(&old_key));
+
+        let first = sealed_block(&blockchain, &genesis, "Bob", None);
+        let second = sealed_block(&blockchain, &first, "Carol", None);
+        assert!(blockchain.consider_fork(VecDeque::from([genesis, first, second])).unwrap());
+        assert_eq!(blockchain.authorities["Alice"].key_at(2), Some(&old_key));
+        assert!(!blockchain.audit_log.iter().any(|entry| entry.change == AuthorityChange::KeyRotated));
+    }
+
+    #[test]
+    fn fork_across_registration_is_rejected() {
+        let mut blockchain = blockchain();
+        let genesis = blockchain.chain[0].clone();
+        seal_next(&mut blockchain);
+        blockchain.register_authority(Authority::new("Dave"));
+
+        let other = sealed_block(&blockchain, &genesis, "Bob", None);
+        assert!(matches!(
+            blockchain.consider_fork(VecDeque::from([genesis, other])),
+            Err(BlockError::ForkCrossesAuthorityChange(0))
+        ));
+    }
+}
@@ -0,0 +1,218 @@
+use super::{Block, BlockError, Blockchain};
+use ed25519_dalek::{Signature, Signer, Verifier};
+
+// Rounds tried at one height before giving up, so a stalled network does not loop forever.
+const MAX_ROUNDS: u64 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageKind {
+    Prepare,
+    Commit,
+    RoundChange,
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub kind: MessageKind,
+    pub round: u64,
+    pub sender: String,
+    pub digest: String,
+    pub commit_seal: Option<Signature>,
+}
+
+impl Blockchain {
+    // QBFT quorum: ceil(2n/3) authorities, which tolerates f faulty ones when n = 3f + 1.
+    pub fn quorum(&self) -> usize {
+        (2 * self.signers().len()).div_ceil(3)
+    }
+
+    // Proposers rotate with both height and round, so a round change moves to the next authority.
+    pub fn proposer(&self, height: u64, round: u64) -> Option<String> {
+        let signers = self.signers();
+        if signers.is_empty() {
+            return None;
+        }
+        Some(signers[((height + round) % signers.len() as u64) as usize].clone())
+    }
+
+    pub fn validate_proposer(&self, block: &Block) -> Result<(), BlockError> {
+        let expected = self.proposer(block.id, block.round).unwrap_or_default();
+        if block.validator != expected {
+            return Err(BlockError::WrongProposer { round: block.round, expected });
+        }
+        Ok(())
+    }
+
+    // A committed block must carry commit seals over its hash from a quorum of distinct signers.
+    pub fn verify_commit_seals(&self, block: &Block) -> Result<(), BlockError> {
+        let signers = self.signers();
+        let mut sealed_by: Vec<&String> = block
+            .commit_seals
+            .iter()
+            .filter(|(signer, seal)| {
+                signers.contains(signer)
+                    && self.authorities[signer]
+                        .key_at(block.id)
+                        .is_some_and(|key| key.verify(block.hash.as_bytes(), seal).is_ok())
+            })
+            .map(|(signer, _)| signer)
+            .collect();
+        sealed_by.sort();
+        sealed_by.dedup();
+        let quorum = self.quorum();
+        if sealed_by.len() < quorum {
+            return Err(BlockError::MissingCommitSeals {
+                id: block.id,
+                found: sealed_by.len(),
+                quorum,
+            });
+        }
+        Ok(())
+    }
+
+    // Runs IBFT rounds for the next height. The round's proposer broadcasts its block; online
+    // authorities that accept it send PREPARE, and after a prepare quorum they send COMMIT with a
+    // commit seal. A commit quorum appends the block with immediate finality. If the proposer is
+    // offline, a quorum of ROUND-CHANGE messages moves to the next round and proposer.
+    pub fn commit_next_block(&mut self) -> Result<(), BlockError> {
+        let height = self.chain.back().map_or(0, |block| block.id + 1);
+        let quorum = self.quorum();
+        let online: Vec<String> = self
+            .signers()
+            .into_iter()
+            .filter(|signer| !self.offline.contains(signer))
+            .collect();
+        let mut messages: Vec<Message> = vec![];
+        let count = |messages: &[Message], kind: MessageKind, round: u64| {
+            messages
+                .iter()
+                .filter(|message| message.kind == kind && message.round == round)
+                .count()
+        };
+
+        for round in 0..MAX_ROUNDS {
+            let proposer = self.proposer(height, round).ok_or(BlockError::NoQuorum {
+                height,
+                online: 0,
+                quorum,
+            })?;
+
+            if !self.offline.contains(&proposer) {
+                let mut block = self.build_block(&proposer, 0, round);
+                if self.validate_block(&self.chain, &block).is_ok() {
+                    for sender in &online {
+                        messages.push(Message {
+                            kind: MessageKind::Prepare,
+                            round,
+                            sender: sender.clone(),
+                            digest: block.hash.clone(),
+                            commit_seal: None,
+                        });
+                    }
+                }
+                if count(&messages, MessageKind::Prepare, round) >= quorum {
+                    for sender in &online {
+                        messages.push(Message {
+                            kind: MessageKind::Commit,
+                            round,
+                            sender: sender.clone(),
+                            digest: block.hash.clone(),
+                            commit_seal: Some(self.authorities[sender].keypair.sign(block.hash.as_bytes())),
+                        });
+                    }
+                }
+                if count(&messages, MessageKind::Commit, round) >= quorum {
+                    block.commit_seals = messages
+                        .iter()
+                        .filter(|message| {
+                            message.kind == MessageKind::Commit && message.round == round && message.digest == block.hash
+                        })
+                        .filter_map(|message| Some((message.sender.clone(), message.commit_seal?)))
+                        .collect();
+                    let seals = block.commit_seals.len();
+                    self.append_block(block)?;
+                    self.pending_transactions.clear();
+                    println!(
+                        "Block {} proposed by {} committed in round {} with {} commit seals",
+                        height, proposer, round, seals
+                    );
+                    return Ok(());
+                }
+            }
+
+            for sender in &online {
+                messages.push(Message {
+                    kind: MessageKind::RoundChange,
+                    round: round + 1,
+                    sender: sender.clone(),
+                    digest: String::new(),
+                    commit_seal: None,
+                });
+            }
+            if count(&messages, MessageKind::RoundChange, round + 1) < quorum {
+                break;
+            }
+            println!("Round change at height {}: {} did not propose in round {}", height, proposer, round);
+        }
+
+        Err(BlockError::NoQuorum {
+            height,
+            online: online.len(),
+            quorum,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clique::tests::{blockchain_with, follower};
+    use crate::{clique, SealingMode};
+    use std::collections::VecDeque;
+
+    // Two IBFT nodes with the same authorities and genesis block; the second has seen nothing since.
+    fn nodes() -> (Blockchain, Blockchain) {
+        let mut leader = blockchain_with(&["Alice", "Bob", "Carol", "Dave"]);
+        leader.sealing_mode = SealingMode::Ibft;
+        let lagging = follower(&leader);
+        (leader, lagging)
+    }
+
+    #[test]
+    fn lagging_node_catches_up_on_committed_blocks() {
+        let (mut leader, mut lagging) = nodes();
+        leader.commit_next_block().unwrap();
+        leader.commit_next_block().unwrap();
+
+        assert!(matches!(lagging.consider_fork(leader.chain.clone()), Ok(true)));
+        assert_eq!(lagging.chain.back().unwrap().hash, leader.chain.back().unwrap().hash);
+    }
+
+    #[test]
+    fn extension_without_commit_seals_is_rejected() {
+        let (mut leader, mut lagging) = nodes();
+        leader.commit_next_block().unwrap();
+        let mut fork = leader.chain.clone();
+        fork[1].commit_seals.clear();
+
+        assert!(matches!(
+            lagging.consider_fork(fork),
+            Err(BlockError::MissingCommitSeals { id: 1, found: 0, .. })
+        ));
+        assert_eq!(lagging.chain.len(), 1);
+    }
+
+    #[test]
+    fn fork_below_head_is_ignored() {
+        let (mut leader, _) = nodes();
+        leader.commit_next_block().unwrap();
+        let committed = leader.chain.back().unwrap().hash.clone();
+        let genesis = leader.chain[0].clone();
+        let proposer = leader.signers().into_iter().find(|signer| *signer != leader.chain[1].validator).unwrap();
+        let mut block = Block::new(1, genesis.hash.clone(), vec![], proposer.clone(), clique::DIFF_IN_TURN);
+        block.seal(&leader.authorities[&proposer].keypair);
+
+        assert!(matches!(leader.consider_fork(VecDeque::from([genesis, block])), Ok(false)));
+        assert_eq!(leader.chain.back().unwrap().hash, committed);
+    }
+}
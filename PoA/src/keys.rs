@@ -0,0 +1,269 @@
+use super::{Authority, AuthorityChange, Block, BlockError, Blockchain};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::fmt;
+
+// Announces `new_key` for `authority` from `height` on, signed with the key it replaces.
+#[derive(Debug, Clone)]
+pub struct KeyRotation {
+    pub authority: String,
+    pub new_key: VerifyingKey,
+    pub height: u64,
+    pub signature: Signature,
+}
+
+impl KeyRotation {
+    // The signature covers the authority and height too, so a rotation cannot be replayed for
+    // another authority or at a later height.
+    fn message(authority: &str, new_key: &VerifyingKey, height: u64) -> Vec<u8> {
+        let mut message = authority.as_bytes().to_vec();
+        message.extend_from_slice(new_key.as_bytes());
+        message.extend_from_slice(&height.to_be_bytes());
+        message
+    }
+
+    // What the block hash commits to, signature included.
+    pub fn encode(&self) -> String {
+        let key: String = self.new_key.as_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+        let signature: String = self.signature.to_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("{}|{}|{}|{}", self.authority, key, self.height, signature)
+    }
+}
+
+#[derive(Debug)]
+pub enum KeyError {
+    UnknownAuthority(String),
+    InvalidRotationSignature(String),
+    KeyRevoked(String),
+    NotAVoter(String),
+    StaleRotation(String, u64),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyError::UnknownAuthority(name) => write!(f, "{} is not an authority", name),
+            KeyError::InvalidRotationSignature(name) => {
+                write!(f, "rotation for {} is not signed by its current key", name)
+            }
+            KeyError::KeyRevoked(name) => write!(f, "the key of {} has been revoked", name),
+            KeyError::NotAVoter(name) => write!(f, "{} cannot vote on this revocation", name),
+            KeyError::StaleRotation(name, height) => {
+                write!(f, "rotation for {} was signed for height {}", name, height)
+            }
+        }
+    }
+}
+
+fn short_key(key: &VerifyingKey) -> String {
+    key.as_bytes()[..4].iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+impl Authority {
+    // The key that seals blocks at `height`, if it was not revoked by then.
+    pub fn key_at(&self, height: u64) -> Option<&VerifyingKey> {
+        if self.revoked_at.is_some_and(|revoked| revoked <= height) {
+            return None;
+        }
+        self.keys
+            .iter()
+            .rev()
+            .find(|(from, _)| *from <= height)
+            .map(|(_, key)| key)
+    }
+
+    // Run on the authority's own node: generates a fresh signing key and the rotation
+    // transaction for `height`, signed with the old key. The node keeps sealing with the old key
+    // until the chain accepts the rotation.
+    pub fn rotate_key(&self, height: u64) -> (KeyRotation, SigningKey) {
+        let new_key = SigningKey::generate(&mut rand::thread_rng());
+        let message = KeyRotation::message(&self.name, &new_key.verifying_key(), height);
+        let rotation = KeyRotation {
+            authority: self.name.clone(),
+            new_key: new_key.verifying_key(),
+            height,
+            signature: self.keypair.sign(&message),
+        };
+        (rotation, new_key)
+    }
+}
+
+impl Blockchain {
+    // Queues a rotation for the next block. It must be signed for the height after that block,
+    // from which the new key takes over; returns that height.
+    pub fn submit_key_rotation(&mut self, rotation: KeyRotation) -> Result<u64, KeyError> {
+        let height = self.chain.back().map_or(0, |block| block.id + 1);
+        self.check_rotation(&rotation, height)?;
+        self.pending_rotations.retain(|pending| pending.authority != rotation.authority);
+        self.pending_rotations.push(rotation);
+        Ok(height + 1)
+    }
+
+    // A rotation carried by the block at `height` is signed by the authority's current key for
+    // the following height.
+    pub fn check_rotation(&self, rotation: &KeyRotation, height: u64) -> Result<(), KeyError> {
+        if rotation.height != height + 1 {
+            return Err(KeyError::StaleRotation(rotation.authority.clone(), rotation.height));
+        }
+        let authority = self
+            .authorities
+            .get(&rotation.authority)
+            .ok_or_else(|| KeyError::UnknownAuthority(rotation.authority.clone()))?;
+        let current = authority
+            .key_at(rotation.height)
+            .ok_or_else(|| KeyError::KeyRevoked(rotation.authority.clone()))?;
+        let message = KeyRotation::message(&rotation.authority, &rotation.new_key, rotation.height);
+        if current.verify(&message, &rotation.signature).is_err() {
+            return Err(KeyError::InvalidRotationSignature(rotation.authority.clone()));
+        }
+        Ok(())
+    }
+
+    // Records that `voter` will vote to revoke `target`'s key in the blocks it seals. Used for
+    // emergency revocation of a compromised key.
+    pub fn propose_revocation(&mut self, voter: &str, target: &str) -> Result<(), KeyError> {
+        self.check_revocation_vote(voter, target)?;
+        let targets = self.revocation_proposals.entry(voter.to_string()).or_default();
+        if !targets.iter().any(|pending| pending == target) {
+            targets.push(target.to_string());
+        }
+        Ok(())
+    }
+
+    // Only the other signers may vote to revoke an authority's key.
+    pub fn check_revocation_vote(&self, voter: &str, target: &str) -> Result<(), KeyError> {
+        if !self.authorities.contains_key(target) {
+            return Err(KeyError::UnknownAuthority(target.to_string()));
+        }
+        if voter == target || !self.signers().iter().any(|signer| signer == voter) {
+            return Err(KeyError::NotAVoter(voter.to_string()));
+        }
+        Ok(())
+    }
+
+    // The sealer's first revocation vote it has not cast yet.
+    pub fn revocation_for_block(&self, sealer: &str) -> Option<String> {
+        self.revocation_proposals
+            .get(sealer)?
+            .iter()
+            .find(|target| {
+                let voted = self.revocation_votes.get(*target).is_some_and(|votes| votes.contains(sealer));
+                let revoked = self.authorities.get(*target).is_some_and(|authority| authority.revoked_at.is_some());
+                !voted && !revoked
+            })
+            .cloned()
+    }
+
+    pub fn validate_key_changes(&self, block: &Block) -> Result<(), BlockError> {
+        let reject = |reason| BlockError::RejectedKeyChange { id: block.id, reason };
+        for rotation in &block.rotations {
+            self.check_rotation(rotation, block.id).map_err(reject)?;
+        }
+        if let Some(target) = &block.revocation {
+            self.check_revocation_vote(&block.validator, target).map_err(reject)?;
+        }
+        Ok(())
+    }
+
+    // Applies the key changes in a newly appended block. Rotated keys take over from the next
+    // block; a revocation passes once more than half of the other signers have voted for it,
+    // after which the target's key stops being valid and it leaves the signer set.
+    pub fn apply_key_changes(&mut self, block: &Block) {
+        for rotation in &block.rotations {
+            let authority = self.authorities.get_mut(&rotation.authority).unwrap();
+            let current = *authority.key_at(rotation.height).unwrap();
+            authority.keys.push((rotation.height, rotation.new_key));
+            self.record_change(rotation.height, &rotation.authority, AuthorityChange::KeyRotated, vec![rotation.authority.clone()]);
+            println!(
+                "{} rotates key {} -> {} at height {}",
+                rotation.authority,
+                short_key(&current),
+                short_key(&rotation.new_key),
+                rotation.height
+            );
+        }
+        self.pending_rotations.retain(|rotation| rotation.height > block.id + 1);
+
+        let Some(target) = &block.revocation else {
+            return;
+        };
+        let others: Vec<String> = self.signers().into_iter().filter(|signer| signer != target).collect();
+        let votes = self.revocation_votes.entry(target.clone()).or_default();
+        votes.insert(block.validator.clone());
+        let support = votes.iter().filter(|voter| others.contains(voter)).count();
+        if support <= others.len() / 2 {
+            return;
+        }
+
+        let height = block.id + 1;
+        let mut voters: Vec<String> = self.revocation_votes.remove(target).unwrap_or_default().into_iter().collect();
+        voters.sort();
+        self.record_change(height, target, AuthorityChange::KeyRevoked, voters);
+        let authority = self.authorities.get_mut(target).unwrap();
+        authority.revoked_at = Some(height);
+        authority.approved = false;
+        self.suspensions.remove(target);
+        println!("Key of {} revoked from height {}", target, height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clique::tests::{blockchain_with, seal_next, sealed_block};
+
+    fn next_height(blockchain: &Blockchain) -> u64 {
+        blockchain.chain.back().unwrap().id + 1
+    }
+
+    #[test]
+    fn key_switches_only_after_rotation_is_sealed() {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol"]);
+        let old_key = blockchain.authorities["Alice"].keypair.verifying_key();
+        let (rotation, new_key) = blockchain.authorities["Alice"].rotate_key(next_height(&blockchain) + 1);
+        let height = blockchain.submit_key_rotation(rotation).unwrap();
+        assert_eq!(blockchain.authorities["Alice"].key_at(height), Some(&old_key));
+
+        seal_next(&mut blockchain);
+        assert_eq!(blockchain.authorities["Alice"].key_at(height), Some(&new_key.verifying_key()));
+        assert_eq!(blockchain.authorities["Alice"].key_at(height - 1), Some(&old_key));
+        assert!(blockchain.pending_rotations.is_empty());
+    }
+
+    #[test]
+    fn rotation_cannot_be_replayed_at_another_height_or_authority() {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol"]);
+        let height = next_height(&blockchain) + 1;
+        let (rotation, _) = blockchain.authorities["Alice"].rotate_key(height - 1);
+        assert!(matches!(
+            blockchain.submit_key_rotation(rotation),
+            Err(KeyError::StaleRotation(_, _))
+        ));
+
+        let (mut rotation, _) = blockchain.authorities["Alice"].rotate_key(height);
+        rotation.authority = "Bob".to_string();
+        assert!(matches!(
+            blockchain.submit_key_rotation(rotation),
+            Err(KeyError::InvalidRotationSignature(_))
+        ));
+        assert!(blockchain.pending_rotations.is_empty());
+    }
+
+    #[test]
+    fn sealed_rotation_cannot_be_replayed_in_a_later_block() {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol"]);
+        let (rotation, _) = blockchain.authorities["Alice"].rotate_key(next_height(&blockchain) + 1);
+        blockchain.submit_key_rotation(rotation.clone()).unwrap();
+        seal_next(&mut blockchain);
+
+        let parent = blockchain.chain.back().unwrap().clone();
+        let signer = blockchain.in_turn_signer(parent.id + 1).unwrap();
+        let mut block = sealed_block(&blockchain, &parent, &signer, None);
+        block.rotations = vec![rotation];
+        block.finalize_block();
+        block.seal(&blockchain.authorities[&signer].keypair);
+        assert!(matches!(
+            blockchain.append_block(block),
+            Err(BlockError::RejectedKeyChange { reason: KeyError::StaleRotation(_, _), .. })
+        ));
+    }
+}
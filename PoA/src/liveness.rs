@@ -0,0 +1,178 @@
+use super::{AuthorityChange, Block, Blockchain, SealingMode};
+
+// One turn an authority was expected to seal, and whether it did.
+#[derive(Debug, Clone)]
+pub struct Duty {
+    pub height: u64,
+    pub authority: String,
+    pub produced: bool,
+}
+
+impl Blockchain {
+    // Authorities whose turn passed up to and including `block`: the in-turn signer for Clique,
+    // every step author since the parent for Aura, and every round's proposer for IBFT.
+    fn expected_authors(&self, parent: &Block, block: &Block) -> Vec<String> {
+        match self.sealing_mode {
+            SealingMode::Clique => self.in_turn_signer(block.id).into_iter().collect(),
+            SealingMode::Aura => (parent.step + 1..=block.step)
+                .filter_map(|step| self.step_author(step))
+                .collect(),
+            SealingMode::Ibft => (0..=block.round)
+                .filter_map(|round| self.proposer(block.id, round))
+                .collect(),
+            SealingMode::Reputation => (parent.step + 1..=block.step)
+                .filter_map(|slot| self.slot_author(slot))
+                .collect(),
+        }
+    }
+
+    // Called for each block before it is appended.
+    pub fn record_duties(&mut self, block: &Block) {
+        let Some(parent) = self.chain.back() else {
+            return;
+        };
+        let expected = self.expected_authors(parent, block);
+        self.decay_reputation();
+        for authority in expected {
+            let produced = authority == block.validator;
+            self.adjust_reputation(&authority, produced);
+            self.duties.push(Duty {
+                height: block.id,
+                produced,
+                authority,
+            });
+        }
+        self.enforce_liveness(block.id);
+    }
+
+    fn missed_in_window(&self, authority: &str, height: u64) -> u64 {
+        let since = self.liveness_reset.get(authority).copied().unwrap_or(0);
+        let start = height.saturating_sub(self.liveness_window).max(since);
+        self.duties
+            .iter()
+            .filter(|duty| duty.authority == authority && duty.height > start && !duty.produced)
+            .count() as u64
+    }
+
+    // Suspends signers that missed `max_missed` turns within the window, and reinstates
+    // suspended ones once their probation is over. Misses before reinstatement are forgiven.
+    // Suspension is kept apart from `approved`, so it never brings back a removed or revoked
+    // authority.
+    fn enforce_liveness(&mut self, height: u64) {
+        let signers = self.signers();
+        for signer in &signers {
+            if signers.len() > 1 && self.missed_in_window(signer, height) >= self.max_missed {
+                let until = height + self.probation_length;
+                self.suspensions.insert(signer.clone(), until);
+                self.record_change(height + 1, signer, AuthorityChange::Suspended, vec![]);
+                println!("{} suspended for missing blocks until height {}", signer, until);
+            }
+        }
+
+        let reinstated: Vec<String> = self
+            .suspensions
+            .iter()
+            .filter(|(_, until)| **until <= height)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in reinstated {
+            self.suspensions.remove(&name);
+            let authority = &self.authorities[&name];
+            if !authority.approved || authority.revoked_at.is_some() {
+                continue;
+            }
+            self.liveness_reset.insert(name.clone(), height);
+            self.record_change(height + 1, &name, AuthorityChange::Reinstated, vec![]);
+            println!("{} reinstated after probation at height {}", name, height);
+        }
+    }
+
+    // Expected turns, blocks sealed, and missed turns over the last `window` blocks.
+    pub fn health_report(&self, window: u64) {
+        let head = self.chain.back().map_or(0, |block| block.id);
+        let start = head.saturating_sub(window);
+        let mut names: Vec<_> = self.authorities.keys().collect();
+        names.sort();
+        println!("Authority health over heights {}..={}:", start + 1, head);
+        for name in names {
+            let duties: Vec<_> = self
+                .duties
+                .iter()
+                .filter(|duty| duty.authority == *name && duty.height > start)
+                .collect();
+            let missed = duties.iter().filter(|duty| !duty.produced).count();
+            let produced = self
+                .chain
+                .iter()
+                .filter(|block| block.id > start && block.validator == *name)
+                .count();
+            let status = match (self.authorities[name].approved, self.suspensions.get(name)) {
+                (false, _) => "removed".to_string(),
+                (true, Some(until)) => format!("suspended until {}", until),
+                (true, None) => "active".to_string(),
+            };
+            println!(
+                "  {}: expected {}, produced {}, missed {} ({})",
+                name,
+                duties.len(),
+                produced,
+                missed,
+                status
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clique::tests::{blockchain_with, seal_next, seal_votes};
+    use crate::voting::Proposal;
+
+    fn remove(target: &str) -> Option<Proposal> {
+        Some(Proposal {
+            target: target.to_string(),
+            authorize: false,
+        })
+    }
+
+    #[test]
+    fn suspended_authority_is_reinstated_after_probation() {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol"]);
+        blockchain.suspensions.insert("Carol".to_string(), 2);
+        assert_eq!(blockchain.signers(), ["Alice", "Bob"]);
+        seal_votes(&mut blockchain, vec![None, None]);
+        assert_eq!(blockchain.signers(), ["Alice", "Bob", "Carol"]);
+    }
+
+    #[test]
+    fn suspended_authority_can_be_voted_out() {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol"]);
+        blockchain.suspensions.insert("Carol".to_string(), 4);
+        blockchain.propose("Alice", "Carol", false);
+        assert_eq!(blockchain.proposal_for_block("Alice"), remove("Carol"));
+
+        seal_votes(&mut blockchain, vec![remove("Carol"), remove("Carol")]);
+        assert!(!blockchain.authorities["Carol"].approved);
+        assert!(blockchain.suspensions.is_empty());
+
+        // Probation would have ended here; the removal still stands.
+        seal_votes(&mut blockchain, vec![None, None]);
+        assert_eq!(blockchain.signers(), ["Alice", "Bob"]);
+    }
+
+    #[test]
+    fn revoked_authority_is_not_reinstated() {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol"]);
+        blockchain.suspensions.insert("Carol".to_string(), 2);
+        blockchain.propose_revocation("Alice", "Carol").unwrap();
+        blockchain.propose_revocation("Bob", "Carol").unwrap();
+        seal_next(&mut blockchain);
+        assert!(blockchain.authorities["Carol"].revoked_at.is_none());
+        seal_next(&mut blockchain);
+        assert!(blockchain.authorities["Carol"].revoked_at.is_some());
+        assert!(blockchain.suspensions.is_empty());
+
+        seal_votes(&mut blockchain, vec![None, None]);
+        assert_eq!(blockchain.signers(), ["Alice", "Bob"]);
+    }
+}
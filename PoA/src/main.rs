@@ -1,22 +1,53 @@
+mod audit;
+mod aura;
+mod clique;
+mod ibft;
+mod keys;
+mod liveness;
+mod permissions;
+mod reputation;
+mod seal;
+mod voting;
+
+use audit::{AuditEntry, AuthorityChange};
+use aura::{SealingMode, StepClock};
 use chrono::{Utc};
+use clique::AuthorityState;
+use keys::{KeyError, KeyRotation};
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
 use sha2::{Sha256, Digest};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use liveness::Duty;
+use permissions::{PermissionError, PermissionVote, Role, Transaction, TransactionKind};
 use std::time::Duration;
+use voting::{Proposal, Vote};
 
 #[derive(Debug, Clone)]
 struct Block {
     id: u64,
     timestamp: String,
     previous_hash: String,
-    transactions: Vec<String>,
+    transactions: Vec<Transaction>,
     hash: String,
     validator: String,
+    difficulty: u64,
+    vote: Option<Proposal>,
+    permission_votes: Vec<PermissionVote>,
+    checkpoint: Option<Vec<String>>,
+    seal: Option<Signature>,
+    step: u64,
+    round: u64,
+    commit_seals: Vec<(String, Signature)>,
+    rotations: Vec<KeyRotation>,
+    // The sealer's vote to revoke this authority's key.
+    revocation: Option<String>,
 }
 
 impl Block {
-    fn new(id: u64, previous_hash: String, transactions: Vec<String>, validator: String) -> Self {
+    fn new(id: u64, previous_hash: String, transactions: Vec<Transaction>, validator: String, difficulty: u64) -> Self {
         let timestamp = Utc::now().to_rfc3339();
         let mut block = Block {
             id,
@@ -25,15 +56,34 @@ impl Block {
             transactions,
             hash: String::new(),
             validator,
+            difficulty,
+            vote: None,
+            permission_votes: vec![],
+            checkpoint: None,
+            seal: None,
+            step: 0,
+            round: 0,
+            commit_seals: vec![],
+            rotations: vec![],
+            revocation: None,
         };
         block.finalize_block();
         block
     }
 
     fn calculate_hash(&self) -> String {
+        let vote = match &self.vote {
+            Some(proposal) => format!("{}{}", proposal.target, proposal.authorize),
+            None => String::new(),
+        };
+        let checkpoint = self.checkpoint.as_ref().map(|signers| signers.join(",")).unwrap_or_default();
+        let transactions: String = self.transactions.iter().map(Transaction::encode).collect();
+        let permission_votes: String = self.permission_votes.iter().map(PermissionVote::encode).collect();
+        let rotations: String = self.rotations.iter().map(KeyRotation::encode).collect();
+        let revocation = self.revocation.clone().unwrap_or_default();
         let data = format!(
-            "{}{}{}{}{}",
-            self.id, self.timestamp, self.previous_hash, self.validator, self.transactions.join("")
+            "{}{}{}{}{}{}{}{}{}{}{}{}{}",
+            self.id, self.timestamp, self.previous_hash, self.validator, self.difficulty, self.step, self.round, vote, permission_votes, checkpoint, transactions, rotations, revocation
         );
         let mut hasher = Sha256::new();
         hasher.update(data);
@@ -45,78 +95,252 @@ impl Block {
     }
 }
 
+#[derive(Debug)]
+enum BlockError {
+    UnknownParent(u64),
+    InvalidHash(u64),
+    UnauthorizedSigner(String),
+    RecentlySigned(String),
+    WrongDifficulty { expected: u64, found: u64 },
+    VoteOnEpochBlock(u64),
+    InvalidCheckpoint(u64),
+    InvalidSeal(u64),
+    ForkCrossesAuthorityChange(u64),
+    ForkBelowCheckpoint(u64),
+    RotatedOutKey { id: u64, authority: String },
+    RejectedTransaction { id: u64, reason: PermissionError },
+    RejectedKeyChange { id: u64, reason: KeyError },
+    FutureStep { step: u64, current: u64 },
+    StepNotIncreasing(u64),
+    WrongStepAuthor { step: u64, expected: String },
+    WrongProposer { round: u64, expected: String },
+    MissingCommitSeals { id: u64, found: usize, quorum: usize },
+    NoQuorum { height: u64, online: usize, quorum: usize },
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockError::UnknownParent(id) => write!(f, "block {} does not extend the chain", id),
+            BlockError::InvalidHash(id) => write!(f, "block {} has an invalid hash", id),
+            BlockError::UnauthorizedSigner(name) => write!(f, "{} is not an authorized signer", name),
+            BlockError::RecentlySigned(name) => write!(f, "{} signed one of the recent blocks", name),
+            BlockError::WrongDifficulty { expected, found } => {
+                write!(f, "difficulty {} does not match expected {}", found, expected)
+            }
+            BlockError::VoteOnEpochBlock(id) => write!(f, "epoch block {} carries a vote", id),
+            BlockError::InvalidCheckpoint(id) => write!(f, "block {} has an invalid authority checkpoint", id),
+            BlockError::InvalidSeal(id) => write!(f, "block {} is not sealed by its authority", id),
+            BlockError::ForkCrossesAuthorityChange(id) => {
+                write!(f, "fork from block {} crosses an off-chain authority registration", id)
+            }
+            BlockError::ForkBelowCheckpoint(id) => {
+                write!(f, "fork from block {} branches off below the last epoch checkpoint", id)
+            }
+            BlockError::RotatedOutKey { id, authority } => {
+                write!(f, "block {} is sealed with a key {} rotated out before that height", id, authority)
+            }
+            BlockError::RejectedTransaction { id, reason } => {
+                write!(f, "block {} carries a rejected transaction: {}", id, reason)
+            }
+            BlockError::RejectedKeyChange { id, reason } => {
+                write!(f, "block {} carries a rejected key change: {}", id, reason)
+            }
+            BlockError::FutureStep { step, current } => {
+                write!(f, "step {} is ahead of the current step {}", step, current)
+            }
+            BlockError::StepNotIncreasing(step) => write!(f, "step {} is not after its parent's step", step),
+            BlockError::WrongStepAuthor { step, expected } => {
+                write!(f, "step {} belongs to {}", step, expected)
+            }
+            BlockError::WrongProposer { round, expected } => {
+                write!(f, "round {} must be proposed by {}", round, expected)
+            }
+            BlockError::MissingCommitSeals { id, found, quorum } => {
+                write!(f, "block {} has {} valid commit seals, {} required", id, found, quorum)
+            }
+            BlockError::NoQuorum { height, online, quorum } => write!(
+                f,
+                "height {} stalled: {} authorities online, quorum is {}",
+                height, online, quorum
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct Authority {
     name: String,
     approved: bool,
+    keypair: SigningKey,
+    // Registered public keys and the height each one takes effect from.
+    keys: Vec<(u64, VerifyingKey)>,
+    revoked_at: Option<u64>,
+    reputation: f64,
 }
 
 impl Authority {
     fn new(name: &str) -> Self {
+        let keypair = SigningKey::generate(&mut rand::thread_rng());
         Authority {
             name: name.to_string(),
             approved: true,
+            keys: vec![(0, keypair.verifying_key())],
+            keypair,
+            revoked_at: None,
+            reputation: 1.0,
         }
     }
 }
 
 struct Blockchain {
     chain: VecDeque<Block>,
-    pending_transactions: Vec<String>,
+    pending_transactions: Vec<Transaction>,
     authorities: HashMap<String, Authority>,
+    offline: HashSet<String>,
+    proposals: HashMap<String, Vec<Proposal>>,
+    votes: Vec<Vote>,
+    epoch_length: u64,
+    sealing_mode: SealingMode,
+    step_clock: StepClock,
+    pending_rotations: Vec<KeyRotation>,
+    revocation_proposals: HashMap<String, Vec<String>>,
+    revocation_votes: HashMap<String, HashSet<String>>,
+    duties: Vec<Duty>,
+    liveness_window: u64,
+    max_missed: u64,
+    probation_length: u64,
+    suspensions: HashMap<String, u64>,
+    liveness_reset: HashMap<String, u64>,
+    audit_log: Vec<AuditEntry>,
+    // Account keys that sign transactions, the roles each account holds, and the number of
+    // transactions each sender has committed.
+    accounts: HashMap<String, VerifyingKey>,
+    permissions: HashMap<String, HashSet<Role>>,
+    permission_proposals: HashMap<String, Vec<PermissionVote>>,
+    permission_votes: HashMap<(String, Role, bool), HashSet<String>>,
+    nonces: HashMap<String, u64>,
+    reputation_decay: f64,
+    reputation_reward: f64,
+    reputation_penalty: f64,
+    invalid_block_penalty: f64,
+    min_reputation: f64,
+    // Authors of the reputation slots up to our head's, and the round-robin credit after them.
+    schedule_credit: HashMap<String, f64>,
+    reputation_schedule: Vec<String>,
+    pre_block_states: BTreeMap<u64, AuthorityState>,
 }
 
 impl Blockchain {
     fn new() -> Self {
-        let genesis_block = Block::new(0, String::from("0"), vec!["Genesis Block".to_string()], "System".to_string());
+        let genesis_block = Block::new(0, String::from("0"), vec![], "System".to_string(), 0);
         let mut blockchain = Blockchain {
             chain: VecDeque::new(),
             pending_transactions: vec![],
             authorities: HashMap::new(),
+            offline: HashSet::new(),
+            proposals: HashMap::new(),
+            votes: vec![],
+            epoch_length: 30,
+            sealing_mode: SealingMode::Clique,
+            step_clock: StepClock::new(Duration::from_secs(5)),
+            pending_rotations: vec![],
+            revocation_proposals: HashMap::new(),
+            revocation_votes: HashMap::new(),
+            duties: vec![],
+            liveness_window: 20,
+            max_missed: 3,
+            probation_length: 10,
+            suspensions: HashMap::new(),
+            liveness_reset: HashMap::new(),
+            audit_log: vec![],
+            accounts: HashMap::new(),
+            permissions: HashMap::new(),
+            permission_proposals: HashMap::new(),
+            permission_votes: HashMap::new(),
+            nonces: HashMap::new(),
+            reputation_decay: 0.05,
+            reputation_reward: 0.1,
+            reputation_penalty: 0.3,
+            invalid_block_penalty: 0.5,
+            min_reputation: 0.2,
+            schedule_credit: HashMap::new(),
+            reputation_schedule: vec![],
+            pre_block_states: BTreeMap::new(),
         };
         blockchain.chain.push_back(genesis_block);
         blockchain
     }
 
     fn register_authority(&mut self, authority: Authority) {
+        let height = self.chain.back().map_or(0, |block| block.id + 1);
+        self.record_change(height, &authority.name, AuthorityChange::Registered, vec![]);
         self.authorities.insert(authority.name.clone(), authority);
     }
 
-    fn add_transaction(&mut self, transaction: String) {
+    fn register_account(&mut self, name: &str, key: VerifyingKey) {
+        self.accounts.insert(name.to_string(), key);
+    }
+
+    // Only signed transactions from allow-listed senders holding the required role, and carrying
+    // the sender's next nonce, are queued.
+    fn add_transaction(&mut self, transaction: Transaction) -> Result<(), PermissionError> {
+        self.check_transaction(&transaction)?;
+        transaction.check_nonce(self.next_nonce(&transaction.sender))?;
         self.pending_transactions.push(transaction);
+        Ok(())
     }
 
-    fn select_validator(&self) -> Option<String> {
-        // İlk onaylanmış otoriteyi seçer.
-        for authority in self.authorities.values() {
-            if authority.approved {
-                return Some(authority.name.clone());
-            }
+    fn set_online(&mut self, name: &str, online: bool) {
+        if online {
+            self.offline.remove(name);
+        } else {
+            self.offline.insert(name.to_string());
         }
-        None
     }
 
     fn finalize_pending_transactions(&mut self) {
-        let validator = match self.select_validator() {
-            Some(v) => v,
-            None => {
-                println!("No approved authorities available to validate transactions.");
+        let (validator, step) = match self.sealing_mode {
+            SealingMode::Clique => {
+                let (validator, delay) = match self.select_validator() {
+                    Some(v) => v,
+                    None => {
+                        println!("No approved authorities available to validate transactions.");
+                        return;
+                    }
+                };
+                // Out-of-turn signers wait before sealing so the in-turn signer has priority.
+                if !delay.is_zero() {
+                    thread::sleep(delay);
+                }
+                (validator, 0)
+            }
+            SealingMode::Aura => match self.next_aura_step() {
+                Some(v) => v,
+                None => return,
+            },
+            SealingMode::Reputation => match self.next_reputation_slot() {
+                Some(v) => v,
+                None => return,
+            },
+            // IBFT appends only after a quorum of authorities commits the proposal.
+            SealingMode::Ibft => {
+                if let Err(err) = self.commit_next_block() {
+                    println!("No block committed: {}", err);
+                }
                 return;
             }
         };
 
-        let last_block = self.chain.back().expect("Blockchain is empty; no last block found.");
-        let new_block = Block::new(
-            last_block.id + 1,
-            last_block.hash.clone(),
-            self.pending_transactions.clone(),
-            validator,
-        );
+        let new_block = self.build_block(&validator, step, 0);
 
         // Çözüm 1: `clone` kullanılarak hatadan kaçınılır
-        self.chain.push_back(new_block.clone()); // Blok eklenmeden önce kopyalanır
+        if let Err(err) = self.append_block(new_block.clone()) { // Blok eklenmeden önce kopyalanır
+            println!("Sealed block rejected: {}", err);
+            return;
+        }
         self.pending_transactions.clear();
-        println!("Block finalized by: {}", new_block.validator); // Blok bilgisi yazdırılır
+        println!("Block finalized by: {} (difficulty {})", new_block.validator, new_block.difficulty); // Blok bilgisi yazdırılır
 
         // Çözüm 2 (yorum satırı olarak): Validator bilgisini saklayarak daha verimli bir çözüm
         // let validator = new_block.validator.clone(); // Validator bilgisi saklanıyor
@@ -124,6 +348,33 @@ impl Blockchain {
         // println!("Block finalized by: {}", validator); // Validator bilgisi kullanılıyor
     }
 
+    // Builds and seals the next block on our head with the pending transactions.
+    fn build_block(&self, validator: &str, step: u64, round: u64) -> Block {
+        let last_block = self.chain.back().expect("Blockchain is empty; no last block found.");
+        let height = last_block.id + 1;
+        let mut new_block = Block::new(
+            height,
+            last_block.hash.clone(),
+            self.pending_transactions.clone(),
+            validator.to_string(),
+            self.expected_difficulty(height, validator),
+        );
+        // Epoch blocks checkpoint the authority list; other blocks may carry the sealer's vote.
+        if self.is_epoch_block(height) {
+            new_block.checkpoint = Some(self.signers());
+        } else {
+            new_block.vote = self.proposal_for_block(validator);
+        }
+        new_block.permission_votes = self.permission_votes_for_block(validator);
+        new_block.rotations = self.pending_rotations.clone();
+        new_block.revocation = self.revocation_for_block(validator);
+        new_block.step = step;
+        new_block.round = round;
+        new_block.finalize_block();
+        new_block.seal(&self.authorities[validator].keypair);
+        new_block
+    }
+
     fn print_chain(&self) {
         for block in &self.chain {
             println!("{:?}", block);
@@ -137,7 +388,13 @@ fn main() {
     let authorities = vec![
         Authority::new("Alice"),
         Authority::new("Bob"),
+        Authority::new("Carol"),
     ];
+    // Account keys that sign transactions, separate from the authorities' sealing keys.
+    let account_keys: HashMap<&str, SigningKey> = ["Alice", "Bob", "Mallory"]
+        .into_iter()
+        .map(|name| (name, SigningKey::generate(&mut rand::thread_rng())))
+        .collect();
 
     {
         let mut blockchain = blockchain.lock().unwrap();
@@ -148,13 +405,297 @@ fn main() {
 
     {
         let mut blockchain = blockchain.lock().unwrap();
-        blockchain.add_transaction("Alice -> Bob: 10 coins".to_string());
-        blockchain.add_transaction("Bob -> Charlie: 5 coins".to_string());
+        for (name, key) in &account_keys {
+            blockchain.register_account(name, key.verifying_key());
+        }
+        // Two of the three authorities vote in their blocks to put Alice and Bob on the sender
+        // allow-list; Bob's Deploy role has only Carol's vote and stays pending.
+        for voter in ["Alice", "Bob"] {
+            for account in ["Alice", "Bob"] {
+                if let Err(err) = blockchain.propose_permission(voter, account, Role::Submit, true) {
+                    println!("Permission vote rejected: {}", err);
+                }
+            }
+        }
+        for (voter, account, role) in [("Carol", "Bob", Role::Deploy), ("Alice", "Alice", Role::Administer), ("Carol", "Alice", Role::Administer), ("Mallory", "Mallory", Role::Submit)] {
+            if let Err(err) = blockchain.propose_permission(voter, account, role, true) {
+                println!("Permission vote rejected: {}", err);
+            }
+        }
+        for _ in 0..3 {
+            blockchain.finalize_pending_transactions();
+        }
+        println!("Sender allow-list:");
+        blockchain.permissions_report();
+
+        let submissions = [
+            ("Alice", "Alice", TransactionKind::Transfer, "Alice -> Bob: 10 coins"),
+            ("Bob", "Bob", TransactionKind::Transfer, "Bob -> Charlie: 5 coins"),
+            ("Mallory", "Mallory", TransactionKind::Transfer, "Mallory -> Mallory: 1000 coins"),
+            ("Mallory", "Alice", TransactionKind::Transfer, "Alice -> Mallory: 1000 coins"),
+            ("Bob", "Bob", TransactionKind::Deploy, "Bob deploys token contract"),
+            ("Bob", "Bob", TransactionKind::Configure, "Bob raises the block transaction limit"),
+            ("Alice", "Alice", TransactionKind::Configure, "Alice raises the block transaction limit"),
+        ];
+        for (signer, sender, kind, payload) in submissions {
+            let transaction = Transaction::new(sender, kind, payload, blockchain.next_nonce(sender), &account_keys[signer]);
+            if let Err(err) = blockchain.add_transaction(transaction) {
+                println!("Transaction rejected: {}", err);
+            }
+        }
+        // Resubmitting Alice's signed transfer is caught by its nonce.
+        let replayed = blockchain.pending_transactions[0].clone();
+        if let Err(err) = blockchain.add_transaction(replayed) {
+            println!("Transaction rejected: {}", err);
+        }
+    }
+
+    // A longer fork made only of out-of-turn blocks loses to the chain with more in-turn blocks.
+    {
+        let mut blockchain = blockchain.lock().unwrap();
+        let mut fork = VecDeque::from([blockchain.chain[0].clone()]);
+        for signer in ["Alice", "Bob", "Carol", "Alice", "Bob"] {
+            let parent = fork.back().unwrap();
+            let height = parent.id + 1;
+            let mut block = Block::new(height, parent.hash.clone(), vec![], signer.to_string(), blockchain.expected_difficulty(height, signer));
+            block.seal(&blockchain.authorities[signer].keypair);
+            fork.push_back(block);
+        }
+        let fork_difficulty = clique::total_difficulty(&fork);
+        match blockchain.consider_fork(fork) {
+            Ok(true) => println!("Switched to fork with total difficulty {}", fork_difficulty),
+            Ok(false) => println!(
+                "Kept chain with total difficulty {} over longer fork with {}",
+                clique::total_difficulty(&blockchain.chain),
+                fork_difficulty
+            ),
+            Err(err) => println!("Rejected fork: {}", err),
+        }
+    }
+
+    // Mallory is not an authority, so a block she seals in Bob's name is rejected.
+    {
+        let mut blockchain = blockchain.lock().unwrap();
+        let last_block = blockchain.chain.back().unwrap();
+        let height = last_block.id + 1;
+        let mut forged = Block::new(height, last_block.hash.clone(), vec![Transaction::new("Bob", TransactionKind::Transfer, "Bob -> Mallory: 100 coins", blockchain.next_nonce("Bob"), &account_keys["Mallory"])], "Bob".to_string(), blockchain.expected_difficulty(height, "Bob"));
+        forged.seal(&Authority::new("Mallory").keypair);
+        if let Err(err) = blockchain.append_block(forged) {
+            println!("Rejected block: {}", err);
+        }
+        if let Some(signer) = blockchain.recover_signer(&blockchain.chain[1]) {
+            println!("Block 1 was sealed by {}", signer);
+        }
+    }
+
+    // Bob is in turn for block 4 but offline, and Alice sealed block 3, so Carol seals out of turn.
+    {
+        let mut blockchain = blockchain.lock().unwrap();
+        blockchain.set_online("Bob", false);
+        blockchain.finalize_pending_transactions();
+        blockchain.set_online("Bob", true);
     }
 
+    // Alice rotates her key in the next block. Blocks she sealed earlier still verify, but a
+    // block sealed with the old key after the rotation is rejected. Bob and Carol then vote in
+    // their blocks to revoke Alice's key outright.
     {
         let mut blockchain = blockchain.lock().unwrap();
+        let old_key = blockchain.authorities["Alice"].keypair.clone();
+        let height = blockchain.chain.back().unwrap().id + 2;
+        let (rotation, new_key) = blockchain.authorities["Alice"].rotate_key(height);
+        match blockchain.submit_key_rotation(rotation) {
+            Ok(height) => println!("Alice's new key will be valid from height {}", height),
+            Err(err) => println!("Rotation rejected: {}", err),
+        }
         blockchain.finalize_pending_transactions();
+        if blockchain.authorities["Alice"].key_at(height) == Some(&new_key.verifying_key()) {
+            blockchain.authorities.get_mut("Alice").unwrap().keypair = new_key;
+        }
+
+        let alice_block = blockchain.chain.iter().find(|block| block.validator == "Alice").cloned().unwrap();
+        println!("Block {} still recovers to {:?}", alice_block.id, blockchain.recover_signer(&alice_block));
+        let last_block = blockchain.chain.back().unwrap();
+        let height = last_block.id + 1;
+        let mut stale = Block::new(height, last_block.hash.clone(), vec![], "Alice".to_string(), blockchain.expected_difficulty(height, "Alice"));
+        stale.seal(&old_key);
+        if let Err(err) = blockchain.append_block(stale) {
+            println!("Rejected block: {}", err);
+        }
+
+        for voter in ["Bob", "Carol"] {
+            match blockchain.propose_revocation(voter, "Alice") {
+                Ok(()) => println!("{} votes to revoke Alice's key", voter),
+                Err(err) => println!("Revocation vote rejected: {}", err),
+            }
+        }
+        while blockchain.authorities["Alice"].revoked_at.is_none() {
+            blockchain.finalize_pending_transactions();
+        }
+        println!("Signers after revocation: {:?}", blockchain.signers());
+    }
+
+    // The audit log still knows Alice could seal block 3, so the block re-validates against the
+    // set of that height even though the current set rejects her.
+    {
+        let blockchain = blockchain.lock().unwrap();
+        println!("Authority audit log:");
+        blockchain.audit_report();
+        let alice_block = blockchain.chain.iter().find(|block| block.validator == "Alice").unwrap();
+        println!("Authorities at height {}: {:?}", alice_block.id, blockchain.authorities_at(alice_block.id));
+        if let Err(err) = blockchain.validate_block(&VecDeque::from([blockchain.chain[alice_block.id as usize - 1].clone()]), alice_block) {
+            println!("Against the current set: {}", err);
+        }
+        let valid = blockchain.chain.iter().skip(1).filter(|block| blockchain.revalidate_block(block).is_ok()).count();
+        println!("{} of {} blocks re-validate against their historical authority set", valid, blockchain.chain.len() - 1);
+    }
+
+    // Bob and Carol agree to add Dave; Alice alone wants Carol removed, so that vote stays
+    // pending until the epoch block discards it.
+    {
+        let mut voting = Blockchain::new();
+        voting.epoch_length = 6;
+        for name in ["Alice", "Bob", "Carol"] {
+            voting.register_authority(Authority::new(name));
+        }
+        voting.propose("Bob", "Dave", true);
+        voting.propose("Carol", "Dave", true);
+        voting.propose("Alice", "Carol", false);
+        for _ in 0..voting.epoch_length - 1 {
+            voting.finalize_pending_transactions();
+        }
+        println!("Signers before the epoch block: {:?}", voting.signers());
+        voting.pending_votes_report();
+        voting.finalize_pending_transactions();
+        println!("Votes pending after the epoch block: {}", voting.votes.len());
+    }
+
+    // Aura: one author per 5 second step. Bob is offline for step 4, so no block is made then.
+    {
+        let mut aura = Blockchain::new();
+        aura.sealing_mode = SealingMode::Aura;
+        for name in ["Alice", "Bob", "Carol"] {
+            aura.register_authority(Authority::new(name));
+        }
+        for step in 1..=6 {
+            aura.set_online("Bob", step != 4);
+            aura.finalize_pending_transactions();
+        }
+        aura.step_report();
+
+        let last_block = aura.chain.back().unwrap().clone();
+        let next_step = aura.step_clock.current_step() + 1;
+        let author = aura.step_author(next_step).unwrap();
+        let mut early = Block::new(last_block.id + 1, last_block.hash.clone(), vec![], author.clone(), aura.expected_difficulty(last_block.id + 1, &author));
+        early.step = next_step;
+        early.finalize_block();
+        early.seal(&aura.authorities[&author].keypair);
+        if let Err(err) = aura.append_block(early) {
+            println!("Rejected block: {}", err);
+        }
+
+        let step_duration = aura.step_clock.step_duration;
+        aura.step_clock.advance(step_duration);
+        let intruder = aura.signers().into_iter().find(|signer| *signer != author).unwrap();
+        let mut wrong = Block::new(last_block.id + 1, last_block.hash.clone(), vec![], intruder.clone(), aura.expected_difficulty(last_block.id + 1, &intruder));
+        wrong.step = next_step;
+        wrong.finalize_block();
+        wrong.seal(&aura.authorities[&intruder].keypair);
+        if let Err(err) = aura.append_block(wrong) {
+            println!("Rejected block: {}", err);
+        }
+    }
+
+    // IBFT with four authorities tolerates one fault: Bob's missed proposal triggers a round
+    // change, and with two authorities offline no quorum forms and the height stalls.
+    {
+        let mut ibft = Blockchain::new();
+        ibft.sealing_mode = SealingMode::Ibft;
+        for name in ["Alice", "Bob", "Carol", "Dave"] {
+            ibft.register_authority(Authority::new(name));
+        }
+        ibft.set_online("Bob", false);
+        ibft.finalize_pending_transactions();
+        ibft.finalize_pending_transactions();
+        ibft.set_online("Carol", false);
+        ibft.finalize_pending_transactions();
+
+        let mut fork = VecDeque::from([ibft.chain[0].clone()]);
+        let mut block = Block::new(1, ibft.chain[0].hash.clone(), vec![], "Bob".to_string(), clique::DIFF_IN_TURN);
+        block.seal(&ibft.authorities["Bob"].keypair);
+        fork.push_back(block);
+        if let Ok(false) = ibft.consider_fork(fork) {
+            println!("Committed blocks are final; fork ignored");
+        }
+
+        // A node that missed the committed blocks catches up by adopting them as an extension.
+        let mut lagging = Blockchain::new();
+        lagging.sealing_mode = SealingMode::Ibft;
+        for name in ["Alice", "Bob", "Carol", "Dave"] {
+            lagging.register_authority(ibft.authorities[name].clone());
+        }
+        lagging.chain = VecDeque::from([ibft.chain[0].clone()]);
+        match lagging.consider_fork(ibft.chain.clone()) {
+            Ok(true) => println!("Lagging node caught up to block {}", lagging.chain.back().unwrap().id),
+            Ok(false) => println!("Lagging node kept its chain"),
+            Err(err) => println!("Lagging node rejected the committed blocks: {}", err),
+        }
+    }
+
+    // Carol goes offline, misses two of her turns within the window and is suspended; she is
+    // back online by the time her probation ends and rejoins the rotation.
+    {
+        let mut monitored = Blockchain::new();
+        monitored.liveness_window = 8;
+        monitored.max_missed = 2;
+        monitored.probation_length = 6;
+        for name in ["Alice", "Bob", "Carol", "Dave"] {
+            monitored.register_authority(Authority::new(name));
+        }
+        monitored.set_online("Carol", false);
+        for height in 1..=14 {
+            if height == 9 {
+                monitored.set_online("Carol", true);
+            }
+            monitored.finalize_pending_transactions();
+        }
+        monitored.health_report(14);
+    }
+
+    // Reputation-weighted schedule: Carol is offline for the first 12 slots and loses slots as
+    // her reputation drops; Bob loses reputation for claiming one of her missed slots.
+    {
+        let mut weighted = Blockchain::new();
+        weighted.sealing_mode = SealingMode::Reputation;
+        weighted.reputation_decay = 0.02;
+        for name in ["Alice", "Bob", "Carol"] {
+            weighted.register_authority(Authority::new(name));
+        }
+        weighted.set_online("Carol", false);
+        let mut rogue_sent = false;
+        for slot in 1..=40 {
+            if slot == 13 {
+                weighted.set_online("Carol", true);
+            }
+            weighted.finalize_pending_transactions();
+
+            // Right after Carol's first missed slot, Bob tries to claim it.
+            let last_block = weighted.chain.back().unwrap().clone();
+            let missed_slot = weighted.step_clock.current_step();
+            if !rogue_sent && last_block.step < missed_slot {
+                rogue_sent = true;
+                let height = last_block.id + 1;
+                let mut rogue = Block::new(height, last_block.hash.clone(), vec![], "Bob".to_string(), weighted.expected_difficulty(height, "Bob"));
+                rogue.step = missed_slot;
+                rogue.finalize_block();
+                rogue.seal(&weighted.authorities["Bob"].keypair);
+                if let Err(err) = weighted.append_block(rogue) {
+                    println!("Rejected block: {}", err);
+                }
+            }
+        }
+        println!("Reputation schedule:");
+        weighted.reputation_report(10);
     }
 
     {
@@ -0,0 +1,360 @@
+use super::{Block, BlockError, Blockchain};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    Submit,
+    Deploy,
+    Administer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransactionKind {
+    Transfer,
+    Deploy,
+    Configure,
+}
+
+impl TransactionKind {
+    // The role a sender must hold to submit this kind of transaction.
+    pub fn required_role(self) -> Role {
+        match self {
+            TransactionKind::Transfer => Role::Submit,
+            TransactionKind::Deploy => Role::Deploy,
+            TransactionKind::Configure => Role::Administer,
+        }
+    }
+}
+
+// A transaction signed by its sender's account key. The nonce counts the sender's earlier
+// transactions, so a signed transaction cannot be included twice.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub sender: String,
+    pub kind: TransactionKind,
+    pub payload: String,
+    pub nonce: u64,
+    pub signature: Signature,
+}
+
+impl Transaction {
+    pub fn new(sender: &str, kind: TransactionKind, payload: &str, nonce: u64, key: &SigningKey) -> Self {
+        Transaction {
+            sender: sender.to_string(),
+            kind,
+            payload: payload.to_string(),
+            nonce,
+            signature: key.sign(&Self::message(sender, kind, payload, nonce)),
+        }
+    }
+
+    fn message(sender: &str, kind: TransactionKind, payload: &str, nonce: u64) -> Vec<u8> {
+        format!("{}|{:?}|{}|{}", sender, kind, payload, nonce).into_bytes()
+    }
+
+    // What the block hash commits to, signature included.
+    pub fn encode(&self) -> String {
+        let signature: String = self.signature.to_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("{}|{:?}|{}|{}|{}", self.sender, self.kind, self.payload, self.nonce, signature)
+    }
+
+    pub fn check_nonce(&self, expected: u64) -> Result<(), PermissionError> {
+        if self.nonce != expected {
+            return Err(PermissionError::WrongNonce {
+                sender: self.sender.clone(),
+                expected,
+                found: self.nonce,
+            });
+        }
+        Ok(())
+    }
+}
+
+// A vote carried in a block header: grant or revoke `role` for `account`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PermissionVote {
+    pub account: String,
+    pub role: Role,
+    pub grant: bool,
+}
+
+impl PermissionVote {
+    pub fn encode(&self) -> String {
+        format!("{}|{:?}|{}", self.account, self.role, self.grant)
+    }
+}
+
+#[derive(Debug)]
+pub enum PermissionError {
+    UnknownSender(String),
+    MissingRole { sender: String, role: Role },
+    NotAnAuthority(String),
+    InvalidSignature(String),
+    WrongNonce { sender: String, expected: u64, found: u64 },
+}
+
+impl fmt::Display for PermissionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PermissionError::UnknownSender(sender) => write!(f, "{} is not on the sender allow-list", sender),
+            PermissionError::MissingRole { sender, role } => write!(f, "{} lacks the {:?} role", sender, role),
+            PermissionError::NotAnAuthority(name) => write!(f, "{} cannot vote on permissions", name),
+            PermissionError::InvalidSignature(sender) => {
+                write!(f, "transaction is not signed by the account key of {}", sender)
+            }
+            PermissionError::WrongNonce { sender, expected, found } => {
+                write!(f, "transaction {} of {} was expected to be {}", found, sender, expected)
+            }
+        }
+    }
+}
+
+impl Blockchain {
+    pub fn check_permission(&self, sender: &str, role: Role) -> Result<(), PermissionError> {
+        let roles = self
+            .permissions
+            .get(sender)
+            .ok_or_else(|| PermissionError::UnknownSender(sender.to_string()))?;
+        if !roles.contains(&role) {
+            return Err(PermissionError::MissingRole {
+                sender: sender.to_string(),
+                role,
+            });
+        }
+        Ok(())
+    }
+
+    // Checks that the sender's registered account key signed the transaction and that the
+    // sender holds the role its kind requires. Run on submission and again for every
+    // transaction in a block received from another authority.
+    pub fn check_transaction(&self, transaction: &Transaction) -> Result<(), PermissionError> {
+        let key = self
+            .accounts
+            .get(&transaction.sender)
+            .ok_or_else(|| PermissionError::UnknownSender(transaction.sender.clone()))?;
+        let message = Transaction::message(&transaction.sender, transaction.kind, &transaction.payload, transaction.nonce);
+        if key.verify(&message, &transaction.signature).is_err() {
+            return Err(PermissionError::InvalidSignature(transaction.sender.clone()));
+        }
+        self.check_permission(&transaction.sender, transaction.kind.required_role())
+    }
+
+    // The nonce the sender's next transaction must carry, counting the ones already queued.
+    pub fn next_nonce(&self, sender: &str) -> u64 {
+        let queued = self.pending_transactions.iter().filter(|transaction| transaction.sender == sender).count();
+        self.nonces.get(sender).copied().unwrap_or(0) + queued as u64
+    }
+
+    // Each sender's transactions in a block must continue from its last committed nonce.
+    pub fn check_block_transactions(&self, block: &Block) -> Result<(), BlockError> {
+        let mut nonces: HashMap<&str, u64> = HashMap::new();
+        for transaction in &block.transactions {
+            let expected = nonces
+                .get(transaction.sender.as_str())
+                .copied()
+                .unwrap_or_else(|| self.nonces.get(&transaction.sender).copied().unwrap_or(0));
+            self.check_transaction(transaction)
+                .and_then(|_| transaction.check_nonce(expected))
+                .map_err(|reason| BlockError::RejectedTransaction { id: block.id, reason })?;
+            nonces.insert(&transaction.sender, expected + 1);
+        }
+        Ok(())
+    }
+
+    // Records that `voter` will vote to grant or revoke `role` for `account` in the blocks it
+    // seals from now on.
+    pub fn propose_permission(&mut self, voter: &str, account: &str, role: Role, grant: bool) -> Result<(), PermissionError> {
+        if !self.signers().iter().any(|signer| signer == voter) {
+            return Err(PermissionError::NotAnAuthority(voter.to_string()));
+        }
+        let proposals = self.permission_proposals.entry(voter.to_string()).or_default();
+        proposals.retain(|vote| !(vote.account == account && vote.role == role));
+        proposals.push(PermissionVote {
+            account: account.to_string(),
+            role,
+            grant,
+        });
+        Ok(())
+    }
+
+    // The sealer's proposals it has not voted for yet and that would still change the allow-list.
+    pub fn permission_votes_for_block(&self, sealer: &str) -> Vec<PermissionVote> {
+        let Some(proposals) = self.permission_proposals.get(sealer) else {
+            return vec![];
+        };
+        proposals
+            .iter()
+            .filter(|vote| {
+                let key = (vote.account.clone(), vote.role, vote.grant);
+                let voted = self.permission_votes.get(&key).is_some_and(|voters| voters.contains(sealer));
+                let held = self.permissions.get(&vote.account).is_some_and(|roles| roles.contains(&vote.role));
+                !voted && vote.grant != held
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Applies a newly appended block: advances its senders' nonces and counts its permission
+    // votes. A change passes once more than half of the current signers back it; an account
+    // left without roles drops off the allow-list.
+    pub fn apply_block_permissions(&mut self, block: &Block) {
+        for transaction in &block.transactions {
+            self.nonces.insert(transaction.sender.clone(), transaction.nonce + 1);
+        }
+        // Queued transactions the block already committed can no longer be included.
+        let nonces = &self.nonces;
+        self.pending_transactions
+            .retain(|transaction| transaction.nonce >= nonces.get(&transaction.sender).copied().unwrap_or(0));
+
+        let signers = self.signers();
+        for vote in &block.permission_votes {
+            let key = (vote.account.clone(), vote.role, vote.grant);
+            let voters = self.permission_votes.entry(key.clone()).or_default();
+            voters.insert(block.validator.clone());
+            if voters.iter().filter(|voter| signers.contains(voter)).count() <= signers.len() / 2 {
+                continue;
+            }
+
+            self.permission_votes.remove(&key);
+            if vote.grant {
+                self.permissions.entry(vote.account.clone()).or_default().insert(vote.role);
+            } else if let Some(roles) = self.permissions.get_mut(&vote.account) {
+                roles.remove(&vote.role);
+                if roles.is_empty() {
+                    self.permissions.remove(&vote.account);
+                }
+            }
+            println!(
+                "Permission vote passed: {} {} the {:?} role",
+                vote.account,
+                if vote.grant { "granted" } else { "revoked" },
+                vote.role
+            );
+        }
+    }
+
+    pub fn permissions_report(&self) {
+        let mut accounts: Vec<_> = self.permissions.iter().collect();
+        accounts.sort_by_key(|(account, _)| account.as_str());
+        for (account, roles) in accounts {
+            let mut roles: Vec<_> = roles.iter().map(|role| format!("{:?}", role)).collect();
+            roles.sort();
+            println!("  {}: {}", account, roles.join(", "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clique::tests::{blockchain_with, seal_next, sealed_block};
+    use std::collections::VecDeque;
+
+    // Alice and Bob vote in their blocks to allow-list `account` for `role`; Bob's account is
+    // registered with `key`.
+    fn blockchain_with_account(key: &SigningKey, role: Role) -> Blockchain {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol"]);
+        blockchain.register_account("Bob", key.verifying_key());
+        for voter in ["Alice", "Bob"] {
+            blockchain.propose_permission(voter, "Bob", role, true).unwrap();
+        }
+        for _ in 0..3 {
+            seal_next(&mut blockchain);
+        }
+        blockchain
+    }
+
+    fn account_key() -> SigningKey {
+        SigningKey::generate(&mut rand::thread_rng())
+    }
+
+    // Seals `transaction` into an in-turn block on top of the head.
+    fn block_with(blockchain: &Blockchain, transaction: Transaction) -> Block {
+        let parent = blockchain.chain.back().unwrap();
+        let height = parent.id + 1;
+        let signer = blockchain.in_turn_signer(height).unwrap();
+        let mut block = Block::new(height, parent.hash.clone(), vec![transaction], signer.clone(), blockchain.expected_difficulty(height, &signer));
+        block.seal(&blockchain.authorities[&signer].keypair);
+        block
+    }
+
+    #[test]
+    fn required_role_comes_from_transaction_kind() {
+        let key = account_key();
+        let mut blockchain = blockchain_with_account(&key, Role::Submit);
+        let transfer = Transaction::new("Bob", TransactionKind::Transfer, "Bob -> Carol: 5 coins", 0, &key);
+        assert!(blockchain.add_transaction(transfer).is_ok());
+
+        let configure = Transaction::new("Bob", TransactionKind::Configure, "Bob raises the gas limit", 1, &key);
+        assert!(matches!(
+            blockchain.add_transaction(configure),
+            Err(PermissionError::MissingRole { role: Role::Administer, .. })
+        ));
+        assert_eq!(blockchain.pending_transactions.len(), 1);
+    }
+
+    #[test]
+    fn transaction_signed_with_another_key_is_rejected() {
+        let mut blockchain = blockchain_with_account(&account_key(), Role::Submit);
+        let forged = Transaction::new("Bob", TransactionKind::Transfer, "Bob -> Mallory: 100 coins", 0, &account_key());
+        assert!(matches!(
+            blockchain.add_transaction(forged),
+            Err(PermissionError::InvalidSignature(_))
+        ));
+    }
+
+    #[test]
+    fn block_with_unpermitted_transaction_is_rejected() {
+        let key = account_key();
+        let mut blockchain = blockchain_with_account(&key, Role::Submit);
+
+        let deploy = Transaction::new("Bob", TransactionKind::Deploy, "Bob deploys a contract", 0, &key);
+        let block = block_with(&blockchain, deploy);
+        assert!(matches!(
+            blockchain.append_block(block),
+            Err(BlockError::RejectedTransaction { id: 4, .. })
+        ));
+
+        let transfer = Transaction::new("Bob", TransactionKind::Transfer, "Bob -> Carol: 5 coins", 0, &key);
+        let block = block_with(&blockchain, transfer);
+        assert!(blockchain.append_block(block).is_ok());
+    }
+
+    #[test]
+    fn replayed_transaction_is_rejected() {
+        let key = account_key();
+        let mut blockchain = blockchain_with_account(&key, Role::Submit);
+        let transfer = Transaction::new("Bob", TransactionKind::Transfer, "Bob -> Carol: 5 coins", 0, &key);
+        blockchain.add_transaction(transfer.clone()).unwrap();
+        assert!(matches!(
+            blockchain.add_transaction(transfer.clone()),
+            Err(PermissionError::WrongNonce { expected: 1, found: 0, .. })
+        ));
+
+        seal_next(&mut blockchain);
+        assert_eq!(blockchain.next_nonce("Bob"), 1);
+        let block = block_with(&blockchain, transfer);
+        assert!(matches!(
+            blockchain.append_block(block),
+            Err(BlockError::RejectedTransaction { reason: PermissionError::WrongNonce { .. }, .. })
+        ));
+    }
+
+    #[test]
+    fn adopted_fork_undoes_permission_votes_of_discarded_branch() {
+        let key = account_key();
+        let mut blockchain = blockchain_with_account(&key, Role::Submit);
+        assert!(blockchain.check_permission("Bob", Role::Submit).is_ok());
+
+        let mut fork = VecDeque::from([blockchain.chain[0].clone()]);
+        for signer in ["Bob", "Carol", "Alice", "Bob"] {
+            let block = sealed_block(&blockchain, fork.back().unwrap(), signer, None);
+            fork.push_back(block);
+        }
+        assert!(blockchain.consider_fork(fork).unwrap());
+        assert!(blockchain.permissions.is_empty());
+        assert!(blockchain.permission_votes.is_empty());
+    }
+}
@@ -0,0 +1,201 @@
+use super::{Block, BlockError, Blockchain, SealingMode};
+use std::collections::HashMap;
+
+impl Blockchain {
+    // Pulls every reputation a little back towards the neutral 1.0, so old behaviour fades.
+    pub fn decay_reputation(&mut self) {
+        let decay = self.reputation_decay;
+        for authority in self.authorities.values_mut() {
+            authority.reputation += (1.0 - authority.reputation) * decay;
+        }
+    }
+
+    pub fn adjust_reputation(&mut self, name: &str, produced: bool) {
+        let change = if produced { self.reputation_reward } else { -self.reputation_penalty };
+        if let Some(authority) = self.authorities.get_mut(name) {
+            authority.reputation = (authority.reputation + change).max(0.0);
+        }
+    }
+
+    // Only penalizes blocks that really were sealed by the named authority, so a forged block
+    // cannot be used to damage someone else's reputation.
+    pub fn penalize_invalid_block(&mut self, block: &Block) {
+        if self.verify_seal(block).is_err() {
+            return;
+        }
+        let penalty = self.invalid_block_penalty;
+        if let Some(authority) = self.authorities.get_mut(&block.validator) {
+            authority.reputation = (authority.reputation - penalty).max(0.0);
+        }
+    }
+
+    // Signers at or above the minimum reputation. If nobody qualifies, all signers stay
+    // eligible rather than halting the chain.
+    fn reputable_signers(&self) -> Vec<String> {
+        let signers = self.signers();
+        let reputable: Vec<String> = signers
+            .iter()
+            .filter(|signer| self.authorities[*signer].reputation >= self.min_reputation)
+            .cloned()
+            .collect();
+        if reputable.is_empty() {
+            signers
+        } else {
+            reputable
+        }
+    }
+
+    // Smooth weighted round-robin: each slot every eligible signer earns credit equal to its
+    // reputation, and the one with the most credit seals and pays back the total. Over time each
+    // signer gets a share of slots proportional to its reputation. Returns the authors of the
+    // slots after the last scheduled one up to `through`, and the credit after them, using the
+    // reputations at our head so that every node derives the same schedule from the chain.
+    fn schedule_slots(&self, through: u64) -> (Vec<String>, HashMap<String, f64>) {
+        let eligible = self.reputable_signers();
+        let total: f64 = eligible.iter().map(|signer| self.authorities[signer].reputation).sum();
+        let mut credit = self.schedule_credit.clone();
+        let mut authors = vec![];
+        for _ in self.reputation_schedule.len() as u64..through {
+            for signer in &eligible {
+                *credit.entry(signer.clone()).or_insert(0.0) += self.authorities[signer].reputation;
+            }
+            let Some(author) = eligible
+                .iter()
+                .max_by(|a, b| credit[*a].total_cmp(&credit[*b]).then(b.cmp(a)))
+                .cloned()
+            else {
+                break;
+            };
+            *credit.get_mut(&author).unwrap() -= total;
+            authors.push(author);
+        }
+        (authors, credit)
+    }
+
+    // Called for each block before it is appended: fixes the authors of the slots up to the
+    // block's own, including the ones that passed without a block.
+    pub fn advance_schedule(&mut self, block: &Block) {
+        if self.sealing_mode != SealingMode::Reputation {
+            return;
+        }
+        let (authors, credit) = self.schedule_slots(block.step);
+        self.reputation_schedule.extend(authors);
+        self.schedule_credit = credit;
+    }
+
+    // Moves the clock to the next slot and returns it with its author, or None if the author is
+    // offline, in which case the slot passes without a block and counts as missed.
+    pub fn next_reputation_slot(&mut self) -> Option<(String, u64)> {
+        self.step_clock.advance(self.step_clock.step_duration);
+        let slot = self.step_clock.current_step();
+        let author = self.slot_author(slot)?;
+        if self.offline.contains(&author) {
+            println!("Slot {} skipped: {} is offline", slot, author);
+            return None;
+        }
+        Some((author, slot))
+    }
+
+    pub fn slot_author(&self, slot: u64) -> Option<String> {
+        let index = slot.checked_sub(1)? as usize;
+        let scheduled = self.reputation_schedule.len();
+        if index < scheduled {
+            return Some(self.reputation_schedule[index].clone());
+        }
+        let (authors, _) = self.schedule_slots(slot);
+        authors.get(index - scheduled).cloned()
+    }
+
+    pub fn validate_slot(&self, parent: &Block, block: &Block) -> Result<(), BlockError> {
+        let current = self.step_clock.current_step();
+        if block.step > current {
+            return Err(BlockError::FutureStep { step: block.step, current });
+        }
+        if parent.id > 0 && block.step <= parent.step {
+            return Err(BlockError::StepNotIncreasing(block.step));
+        }
+        let expected = self.slot_author(block.step).unwrap_or_default();
+        if block.validator != expected {
+            return Err(BlockError::WrongStepAuthor { step: block.step, expected });
+        }
+        Ok(())
+    }
+
+    // Current reputations, then each authority's share of the schedule per `window` slots.
+    pub fn reputation_report(&self, window: usize) {
+        let mut names: Vec<_> = self.authorities.keys().cloned().collect();
+        names.sort();
+        for name in &names {
+            println!("  {}: reputation {:.2}", name, self.authorities[name].reputation);
+        }
+        for (index, slots) in self.reputation_schedule.chunks(window).enumerate() {
+            let mut counts: HashMap<&str, usize> = HashMap::new();
+            for author in slots {
+                *counts.entry(author.as_str()).or_default() += 1;
+            }
+            let shares: Vec<String> = names
+                .iter()
+                .map(|name| format!("{} {}", name, counts.get(name.as_str()).unwrap_or(&0)))
+                .collect();
+            let first = index * window + 1;
+            println!("  slots {}-{}: {}", first, first + slots.len() - 1, shares.join(", "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clique::tests::{blockchain_with, follower};
+
+    fn producer() -> Blockchain {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol"]);
+        blockchain.sealing_mode = SealingMode::Reputation;
+        blockchain
+    }
+
+    fn advance_slots(blockchain: &mut Blockchain, slots: u32) {
+        let step_duration = blockchain.step_clock.step_duration;
+        blockchain.step_clock.advance(step_duration * slots);
+    }
+
+    #[test]
+    fn receiving_node_derives_the_schedule_from_the_chain() {
+        let mut producer = producer();
+        let mut receiver = follower(&producer);
+        producer.set_online("Carol", false);
+        for _ in 0..12 {
+            producer.finalize_pending_transactions();
+        }
+
+        advance_slots(&mut receiver, 12);
+        for block in producer.chain.iter().skip(1) {
+            receiver.append_block(block.clone()).unwrap();
+        }
+        assert_eq!(receiver.reputation_schedule, producer.reputation_schedule);
+        assert_eq!(receiver.schedule_credit, producer.schedule_credit);
+        assert!(receiver.authorities["Carol"].reputation < receiver.authorities["Alice"].reputation);
+    }
+
+    #[test]
+    fn adopted_fork_replays_the_schedule() {
+        let mut ours = producer();
+        let mut theirs = follower(&ours);
+        for _ in 0..4 {
+            ours.finalize_pending_transactions();
+        }
+        // On the other branch the author of slot 2 is offline, so that slot passes without a block.
+        let absent = theirs.slot_author(2).unwrap();
+        for slot in 1..=6 {
+            theirs.set_online(&absent, slot != 2);
+            theirs.finalize_pending_transactions();
+        }
+        assert_eq!(theirs.chain.len(), 6);
+
+        advance_slots(&mut ours, 2);
+        assert!(ours.consider_fork(theirs.chain.clone()).unwrap());
+        assert_eq!(ours.reputation_schedule, theirs.reputation_schedule);
+        assert_eq!(ours.schedule_credit, theirs.schedule_credit);
+        assert_eq!(ours.authorities[&absent].reputation, theirs.authorities[&absent].reputation);
+    }
+}
@@ -0,0 +1,72 @@
+use super::{Block, BlockError, Blockchain, SealingMode};
+use ed25519_dalek::{Signer, SigningKey, Verifier};
+
+impl Block {
+    // Signs the header hash, which covers every field except the seal itself.
+    pub fn seal(&mut self, key: &SigningKey) {
+        self.seal = Some(key.sign(self.hash.as_bytes()));
+    }
+}
+
+impl Blockchain {
+    // Ed25519 signatures cannot be inverted to a public key the way Clique's secp256k1 seals can,
+    // so the signer is recovered by finding the authority whose key at that height verifies it.
+    pub fn recover_signer(&self, block: &Block) -> Option<String> {
+        let seal = block.seal.as_ref()?;
+        self.authorities
+            .values()
+            .find(|authority| {
+                authority
+                    .key_at(block.id)
+                    .is_some_and(|key| key.verify(block.hash.as_bytes(), seal).is_ok())
+            })
+            .map(|authority| authority.name.clone())
+    }
+
+    pub fn verify_seal(&self, block: &Block) -> Result<(), BlockError> {
+        match self.recover_signer(block) {
+            Some(signer) if signer == block.validator => Ok(()),
+            _ => {
+                // Distinguish a seal made with one of the validator's retired keys.
+                let retired = block.seal.as_ref().zip(self.authorities.get(&block.validator)).is_some_and(
+                    |(seal, authority)| {
+                        authority
+                            .keys
+                            .iter()
+                            .any(|(_, key)| key.verify(block.hash.as_bytes(), seal).is_ok())
+                    },
+                );
+                if retired {
+                    Err(BlockError::RotatedOutKey {
+                        id: block.id,
+                        authority: block.validator.clone(),
+                    })
+                } else {
+                    Err(BlockError::InvalidSeal(block.id))
+                }
+            }
+        }
+    }
+
+    // Validates a block received from another authority and appends it to our chain.
+    pub fn append_block(&mut self, block: Block) -> Result<(), BlockError> {
+        if let Err(err) = self.validate_block(&self.chain, &block) {
+            self.penalize_invalid_block(&block);
+            return Err(err);
+        }
+        if self.sealing_mode == SealingMode::Ibft {
+            self.verify_commit_seals(&block)?;
+        }
+        self.pre_block_states.insert(block.id, self.authority_state());
+        // Forks may not branch off below the last epoch checkpoint, so older snapshots are dropped.
+        let checkpoint = block.id - block.id % self.epoch_length;
+        self.pre_block_states = self.pre_block_states.split_off(&checkpoint);
+        self.advance_schedule(&block);
+        self.record_duties(&block);
+        self.apply_block_votes(&block);
+        self.apply_key_changes(&block);
+        self.apply_block_permissions(&block);
+        self.chain.push_back(block);
+        Ok(())
+    }
+}
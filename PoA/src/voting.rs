@@ -0,0 +1,185 @@
+use super::{Authority, AuthorityChange, Block, BlockError, Blockchain};
+
+// A vote carried in a block header: add (`authorize`) or remove `target` from the authority set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proposal {
+    pub target: String,
+    pub authorize: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Vote {
+    pub voter: String,
+    pub proposal: Proposal,
+}
+
+impl Blockchain {
+    pub fn is_epoch_block(&self, height: u64) -> bool {
+        height > 0 && height.is_multiple_of(self.epoch_length)
+    }
+
+    // Records what `authority` will vote for in the blocks it seals from now on.
+    pub fn propose(&mut self, authority: &str, target: &str, authorize: bool) {
+        let proposals = self.proposals.entry(authority.to_string()).or_default();
+        proposals.retain(|proposal| proposal.target != target);
+        proposals.push(Proposal {
+            target: target.to_string(),
+            authorize,
+        });
+    }
+
+    // The sealer's first proposal that would still change the authority set. Suspended
+    // authorities are still members, so they can be voted out.
+    pub fn proposal_for_block(&self, sealer: &str) -> Option<Proposal> {
+        self.proposals.get(sealer)?.iter().find(|proposal| {
+            let voted = self.votes.iter().any(|vote| vote.voter == sealer && vote.proposal == **proposal);
+            let member = self.authorities.get(&proposal.target).is_some_and(|authority| authority.approved);
+            !voted && proposal.authorize != member
+        }).cloned()
+    }
+
+    // Epoch blocks must list exactly the current signers and cannot carry a vote.
+    pub fn validate_epoch_fields(&self, block: &Block) -> Result<(), BlockError> {
+        if self.is_epoch_block(block.id) {
+            if block.vote.is_some() {
+                return Err(BlockError::VoteOnEpochBlock(block.id));
+            }
+            if block.checkpoint.as_ref() != Some(&self.signers()) {
+                return Err(BlockError::InvalidCheckpoint(block.id));
+            }
+        } else if block.checkpoint.is_some() {
+            return Err(BlockError::InvalidCheckpoint(block.id));
+        }
+        Ok(())
+    }
+
+    // Applies the vote in a newly appended block. A proposal passes once more than half of the
+    // current signers back it; epoch blocks discard every pending vote.
+    pub fn apply_block_votes(&mut self, block: &Block) {
+        if self.is_epoch_block(block.id) {
+            self.votes.clear();
+            return;
+        }
+        let Some(proposal) = &block.vote else {
+            return;
+        };
+
+        self.votes
+            .retain(|vote| !(vote.voter == block.validator && vote.proposal.target == proposal.target));
+        self.votes.push(Vote {
+            voter: block.validator.clone(),
+            proposal: proposal.clone(),
+        });
+
+        let signers = self.signers();
+        let support = self
+            .votes
+            .iter()
+            .filter(|vote| vote.proposal == *proposal && signers.contains(&vote.voter))
+            .count();
+        if support <= signers.len() / 2 {
+            return;
+        }
+        let voters: Vec<String> = self
+            .votes
+            .iter()
+            .filter(|vote| vote.proposal == *proposal && signers.contains(&vote.voter))
+            .map(|vote| vote.voter.clone())
+            .collect();
+
+        if proposal.authorize {
+            self.authorities
+                .entry(proposal.target.clone())
+                .or_insert_with(|| Authority::new(&proposal.target))
+                .approved = true;
+            self.record_change(block.id + 1, &proposal.target, AuthorityChange::Approved, voters);
+            println!("Vote passed: {} added as an authority", proposal.target);
+        } else {
+            if let Some(authority) = self.authorities.get_mut(&proposal.target) {
+                authority.approved = false;
+            }
+            self.suspensions.remove(&proposal.target);
+            self.record_change(block.id + 1, &proposal.target, AuthorityChange::Removed, voters);
+            // Votes cast by the removed signer no longer count.
+            self.votes.retain(|vote| vote.voter != proposal.target);
+            println!("Vote passed: {} removed from the authorities", proposal.target);
+        }
+        self.votes.retain(|vote| vote.proposal.target != proposal.target);
+    }
+
+    pub fn pending_votes_report(&self) {
+        for vote in &self.votes {
+            println!(
+                "  pending: {} votes to {} {}",
+                vote.voter,
+                if vote.proposal.authorize { "add" } else { "remove" },
+                vote.proposal.target
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clique::tests::{blockchain_with, seal_votes, sealed_block};
+
+    fn proposal(target: &str, authorize: bool) -> Option<Proposal> {
+        Some(Proposal {
+            target: target.to_string(),
+            authorize,
+        })
+    }
+
+    #[test]
+    fn majority_adds_authority() {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol"]);
+        seal_votes(&mut blockchain, vec![proposal("Dave", true)]);
+        assert!(!blockchain.signers().contains(&"Dave".to_string()));
+        seal_votes(&mut blockchain, vec![proposal("Dave", true)]);
+        assert!(blockchain.signers().contains(&"Dave".to_string()));
+        assert!(blockchain.votes.is_empty());
+    }
+
+    #[test]
+    fn minority_removal_stays_pending() {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol"]);
+        seal_votes(&mut blockchain, vec![proposal("Carol", false), None]);
+        assert!(blockchain.signers().contains(&"Carol".to_string()));
+        assert_eq!(blockchain.votes.len(), 1);
+        assert_eq!(blockchain.votes[0].voter, "Bob");
+    }
+
+    #[test]
+    fn epoch_block_clears_pending_votes() {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol"]);
+        blockchain.epoch_length = 3;
+        seal_votes(&mut blockchain, vec![proposal("Carol", false), None]);
+        assert_eq!(blockchain.votes.len(), 1);
+
+        let parent = blockchain.chain.back().unwrap().clone();
+        let mut epoch_block = sealed_block(&blockchain, &parent, "Alice", None);
+        epoch_block.checkpoint = Some(blockchain.signers());
+        epoch_block.finalize_block();
+        epoch_block.seal(&blockchain.authorities["Alice"].keypair);
+        blockchain.append_block(epoch_block).unwrap();
+        assert!(blockchain.votes.is_empty());
+        assert_eq!(blockchain.signers().len(), 3);
+    }
+
+    #[test]
+    fn removed_signer_votes_no_longer_count() {
+        let mut blockchain = blockchain_with(&["Alice", "Bob", "Carol", "Dave"]);
+        // Bob, Dave and Alice remove Carol; Carol's own vote for Erin is dropped with her.
+        seal_votes(
+            &mut blockchain,
+            vec![proposal("Carol", false), proposal("Erin", true), proposal("Carol", false), proposal("Carol", false)],
+        );
+        assert!(!blockchain.signers().contains(&"Carol".to_string()));
+        assert!(blockchain.votes.iter().all(|vote| vote.voter != "Carol"));
+
+        // One more vote for Erin is now one of three, not two of three.
+        seal_votes(&mut blockchain, vec![proposal("Erin", true)]);
+        assert!(!blockchain.signers().contains(&"Erin".to_string()));
+    }
+}
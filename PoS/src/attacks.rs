@@ -0,0 +1,222 @@
+use super::{Block, Blockchain, Checkpoint, Node};
+use std::collections::{HashMap, HashSet};
+
+pub struct AttackOutcome {
+    scenario: &'static str,
+    countermeasure: bool,
+    honest_fooled: bool,
+    detail: String,
+}
+
+impl AttackOutcome {
+    pub fn report(&self) {
+        println!(
+            "[{}] countermeasure: {} | honest nodes fooled: {} | {}",
+            self.scenario,
+            if self.countermeasure { "on" } else { "off" },
+            self.honest_fooled,
+            self.detail
+        );
+    }
+}
+
+// A validator's vote for a block at a given height.
+#[derive(Debug, Clone)]
+struct Vote {
+    validator: String,
+    height: u64,
+    block_hash: String,
+}
+
+// Validators that voted for two different blocks at the same height.
+fn detect_equivocations(votes: &[Vote]) -> Vec<String> {
+    let mut seen: HashMap<(&str, u64), &str> = HashMap::new();
+    let mut offenders = HashSet::new();
+    for vote in votes {
+        let key = (vote.validator.as_str(), vote.height);
+        match seen.get(&key) {
+            Some(hash) if *hash != vote.block_hash => {
+                offenders.insert(vote.validator.clone());
+            }
+            Some(_) => {}
+            None => {
+                seen.insert(key, &vote.block_hash);
+            }
+        }
+    }
+    let mut offenders: Vec<_> = offenders.into_iter().collect();
+    offenders.sort();
+    offenders
+}
+
+// Stake-weighted vote count for a block, ignoring validators in `excluded`.
+fn vote_weight(blockchain: &Blockchain, votes: &[Vote], block_hash: &str, excluded: &[String]) -> u64 {
+    votes
+        .iter()
+        .filter(|vote| vote.block_hash == block_hash && !excluded.contains(&vote.validator))
+        .map(|vote| blockchain.nodes.get(&vote.validator).copied().unwrap_or(0))
+        .sum()
+}
+
+fn is_valid_chain(chain: &[Block]) -> bool {
+    let mut previous_hash = String::from("0");
+    for block in chain {
+        if block.previous_hash != previous_hash || block.hash != block.calculate_hash() {
+            return false;
+        }
+        previous_hash = block.hash.clone();
+    }
+    true
+}
+
+// Longest valid chain wins; with a checkpoint, chains that do not contain it are discarded first.
+fn choose_chain<'a>(candidates: &[&'a [Block]], checkpoint: Option<&Checkpoint>) -> Option<&'a [Block]> {
+    candidates
+        .iter()
+        .copied()
+        .filter(|chain| is_valid_chain(chain))
+        .filter(|chain| checkpoint.is_none_or(|cp| cp.contained_in(chain)))
+        .max_by_key(|chain| chain.len())
+}
+
+fn mine_blocks(blockchain: &mut Blockchain, count: usize, label: &str) {
+    blockchain.refresh_sampler();
+    for i in 0..count {
+        blockchain.add_transaction(format!("{} tx {}", label, i));
+        if let Some(validator) = blockchain.select_validator() {
+            blockchain.append_block(validator);
+        }
+    }
+}
+
+// Mallory pays Bob and, on a competing fork, pays herself instead. Without slashing the honest
+// validators vote on both forks because it costs them nothing, which hands the fork to Mallory.
+// With slashing they vote once; a validator that still equivocates is caught and its votes dropped.
+pub fn nothing_at_stake(slashing: bool) -> AttackOutcome {
+    let mut blockchain = Blockchain::new();
+    for node in [Node::new("Alice", 40), Node::new("Bob", 35), Node::new("Mallory", 25)] {
+        blockchain.register_node(node);
+    }
+    mine_blocks(&mut blockchain, 2, "prefix");
+
+    let height = blockchain.chain.len() as u64 + 1;
+    let parent = blockchain.last_hash();
+    let honest_block = Block::new(
+        height,
+        height,
+        parent.clone(),
+        vec!["Mallory -> Bob: 20 coins".to_string()],
+        "Alice".to_string(),
+    );
+    let attack_block = Block::new(
+        height,
+        height,
+        parent,
+        vec!["Mallory -> Mallory: 20 coins".to_string()],
+        "Mallory".to_string(),
+    );
+
+    let vote = |validator: &str, block: &Block| Vote {
+        validator: validator.to_string(),
+        height,
+        block_hash: block.hash.clone(),
+    };
+    let mut votes = vec![vote("Mallory", &attack_block)];
+    for validator in ["Alice", "Bob"] {
+        votes.push(vote(validator, &honest_block));
+        // Bob keeps hedging even when slashing is on, to show that it gets caught.
+        if !slashing || validator == "Bob" {
+            votes.push(vote(validator, &attack_block));
+        }
+    }
+
+    let mut excluded = vec![];
+    let mut burned = 0;
+    if slashing {
+        excluded = detect_equivocations(&votes);
+        for offender in &excluded {
+            burned += blockchain.slash(offender, 50);
+        }
+    }
+
+    let honest_weight = vote_weight(&blockchain, &votes, &honest_block.hash, &excluded);
+    let attack_weight = vote_weight(&blockchain, &votes, &attack_block.hash, &excluded);
+    AttackOutcome {
+        scenario: "nothing-at-stake",
+        countermeasure: slashing,
+        honest_fooled: attack_weight > honest_weight,
+        detail: format!(
+            "honest fork weight {} vs attacker fork weight {}, slashed {:?} ({} burned)",
+            honest_weight, attack_weight, excluded, burned
+        ),
+    }
+}
+
+// A chain built from genesis with the long-gone genesis validators' keys.
+pub fn forged_chain(length: usize) -> Vec<Block> {
+    let mut forged = Blockchain::new();
+    for node in [Node::new("Alice", 60), Node::new("Bob", 40)] {
+        forged.register_node(node);
+    }
+    mine_blocks(&mut forged, length, "forged");
+    forged.chain
+}
+
+// Alice and Bob secure the early chain, then unbond and hand over to Carol and Dave. Their old keys
+// no longer guard any stake, so Mallory buys them and forges a longer chain from genesis. A new node
+// syncing from genesis picks the longest chain unless it holds a recent weak-subjectivity checkpoint.
+pub fn long_range(checkpoints: bool) -> AttackOutcome {
+    let mut honest = Blockchain::new();
+    for node in [Node::new("Alice", 60), Node::new("Bob", 40)] {
+        honest.register_node(node);
+    }
+    mine_blocks(&mut honest, 3, "honest");
+    for node in [Node::new("Carol", 70), Node::new("Dave", 30)] {
+        honest.register_node(node);
+    }
+    honest.request_exit("Alice");
+    honest.request_exit("Bob");
+    for epoch in 0..2 {
+        honest.process_validator_queues(epoch);
+    }
+    mine_blocks(&mut honest, 5, "honest");
+
+    let checkpoint = honest.latest_checkpoint().unwrap();
+    let forged = forged_chain(12);
+
+    let adopted = choose_chain(
+        &[&honest.chain, &forged],
+        if checkpoints { Some(&checkpoint) } else { None },
+    );
+    let honest_fooled = adopted.is_none_or(|chain| chain.as_ptr() == forged.as_ptr());
+    AttackOutcome {
+        scenario: "long-range",
+        countermeasure: checkpoints,
+        honest_fooled,
+        detail: format!(
+            "honest chain {} blocks, forged chain {} blocks, checkpoint at height {}",
+            honest.chain.len(),
+            forged.len(),
+            checkpoint.block.id
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_at_stake_fools_honest_nodes_only_without_slashing() {
+        assert!(nothing_at_stake(false).honest_fooled);
+        let outcome = nothing_at_stake(true);
+        assert!(!outcome.honest_fooled);
+        assert!(outcome.detail.contains("[\"Bob\"]"));
+    }
+
+    #[test]
+    fn long_range_fools_honest_nodes_only_without_checkpoints() {
+        assert!(long_range(false).honest_fooled);
+        assert!(!long_range(true).honest_fooled);
+    }
+}
@@ -0,0 +1,154 @@
+use super::{Block, Blockchain};
+use std::collections::HashMap;
+use std::fmt;
+
+// A trusted finalized block together with the validator set that was active at that block.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub block: Block,
+    pub validators: HashMap<String, u64>,
+}
+
+impl Checkpoint {
+    pub fn contained_in(&self, chain: &[Block]) -> bool {
+        chain
+            .iter()
+            .any(|block| block.id == self.block.id && block.hash == self.block.hash)
+    }
+}
+
+#[derive(Debug)]
+pub enum SyncError {
+    CheckpointTooOld { checkpoint_slot: u64, current_slot: u64, period: u64 },
+    MissingCheckpoint { height: u64 },
+    InvalidBlock { id: u64 },
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncError::CheckpointTooOld {
+                checkpoint_slot,
+                current_slot,
+                period,
+            } => write!(
+                f,
+                "checkpoint at slot {} is older than the weak-subjectivity period of {} slots (now slot {})",
+                checkpoint_slot, period, current_slot
+            ),
+            SyncError::MissingCheckpoint { height } => {
+                write!(f, "chain does not contain the checkpoint block at height {}", height)
+            }
+            SyncError::InvalidBlock { id } => write!(f, "block {} does not link to the synced chain", id),
+        }
+    }
+}
+
+impl Blockchain {
+    // Checkpoint of the current head and validator set, for other nodes to start from.
+    pub fn latest_checkpoint(&self) -> Option<Checkpoint> {
+        self.chain.last().map(|block| Checkpoint {
+            block: block.clone(),
+            validators: self.nodes.clone(),
+        })
+    }
+
+    // Starts a node from `checkpoint` instead of genesis. Refuses checkpoints older than the
+    // weak-subjectivity period, since the validators that signed them may have withdrawn since.
+    pub fn from_checkpoint(checkpoint: Checkpoint, current_slot: u64) -> Result<Blockchain, SyncError> {
+        let mut blockchain = Blockchain::new();
+        let period = blockchain.weak_subjectivity_period;
+        if current_slot.saturating_sub(checkpoint.block.slot) > period {
+            return Err(SyncError::CheckpointTooOld {
+                checkpoint_slot: checkpoint.block.slot,
+                current_slot,
+                period,
+            });
+        }
+
+        blockchain.clock.advance_to(checkpoint.block.slot);
+        blockchain.nodes = checkpoint.validators.clone();
+        let epoch = blockchain.clock.epoch_of(checkpoint.block.slot);
+        blockchain.validator_history.insert(epoch, checkpoint.validators.clone());
+        blockchain.chain.push(checkpoint.block.clone());
+        blockchain.checkpoint = Some(checkpoint);
+        Ok(blockchain)
+    }
+
+    // Appends the peer's blocks after our head. Any chain that does not contain our checkpoint
+    // is rejected outright, however long it is.
+    pub fn sync_from(&mut self, peer_chain: &[Block]) -> Result<usize, SyncError> {
+        if let Some(checkpoint) = &self.checkpoint {
+            if !checkpoint.contained_in(peer_chain) {
+                return Err(SyncError::MissingCheckpoint {
+                    height: checkpoint.block.id,
+                });
+            }
+        }
+
+        let head_id = self.chain.last().map_or(0, |block| block.id);
+        let mut synced = 0;
+        for block in peer_chain.iter().filter(|block| block.id > head_id) {
+            if block.previous_hash != self.last_hash() || block.hash != block.calculate_hash() {
+                return Err(SyncError::InvalidBlock { id: block.id });
+            }
+            self.clock.advance_to(block.slot);
+            self.chain.push(block.clone());
+            synced += 1;
+        }
+        Ok(synced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attacks;
+    use crate::Node;
+
+    fn chain_with_blocks(count: usize) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for node in [Node::new("Alice", 60), Node::new("Bob", 40)] {
+            blockchain.register_node(node);
+        }
+        for _ in 0..count {
+            blockchain.clock.tick();
+            blockchain.append_block("Alice".to_string());
+        }
+        blockchain
+    }
+
+    #[test]
+    fn checkpoint_past_weak_subjectivity_period_is_refused() {
+        let blockchain = chain_with_blocks(3);
+        let checkpoint = blockchain.latest_checkpoint().unwrap();
+        let stale_slot = checkpoint.block.slot + blockchain.weak_subjectivity_period + 1;
+        assert!(matches!(
+            Blockchain::from_checkpoint(checkpoint, stale_slot),
+            Err(SyncError::CheckpointTooOld { .. })
+        ));
+    }
+
+    #[test]
+    fn chain_without_checkpoint_is_rejected() {
+        let blockchain = chain_with_blocks(3);
+        let checkpoint = blockchain.latest_checkpoint().unwrap();
+        let mut node = Blockchain::from_checkpoint(checkpoint, blockchain.clock.current_slot()).unwrap();
+        let forged = attacks::forged_chain(blockchain.chain.len() + 5);
+        assert!(matches!(node.sync_from(&forged), Err(SyncError::MissingCheckpoint { height: 3 })));
+        assert_eq!(node.chain.len(), 1);
+    }
+
+    #[test]
+    fn node_syncs_blocks_after_checkpoint() {
+        let mut blockchain = chain_with_blocks(3);
+        let checkpoint = blockchain.latest_checkpoint().unwrap();
+        for _ in 0..2 {
+            blockchain.clock.tick();
+            blockchain.append_block("Bob".to_string());
+        }
+        let mut node = Blockchain::from_checkpoint(checkpoint, blockchain.clock.current_slot()).unwrap();
+        assert_eq!(node.sync_from(&blockchain.chain).unwrap(), 2);
+        assert_eq!(node.last_hash(), blockchain.last_hash());
+    }
+}
@@ -0,0 +1,233 @@
+use super::Blockchain;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+const SHUFFLE_ROUNDS: u8 = 90;
+const TARGET_COMMITTEE_SIZE: usize = 4;
+
+fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+// Swap-or-not shuffle: the position `index` moves to in a permutation of `count` items.
+// Every round pairs each index with `pivot - index` and swaps the pair when a seed-derived bit is set.
+pub fn shuffled_index(mut index: usize, count: usize, seed: &[u8; 32]) -> usize {
+    for round in 0..SHUFFLE_ROUNDS {
+        let pivot_hash = sha256(&[seed, &[round]]);
+        let pivot = (u64::from_le_bytes(pivot_hash[..8].try_into().unwrap()) % count as u64) as usize;
+        let flip = (pivot + count - index) % count;
+        let position = index.max(flip);
+        let source = sha256(&[seed, &[round], &((position / 256) as u32).to_le_bytes()]);
+        let byte = source[(position % 256) / 8];
+        if (byte >> (position % 8)) & 1 == 1 {
+            index = flip;
+        }
+    }
+    index
+}
+
+// One committee's attestations to the head block, aggregated into a participation bitfield.
+#[derive(Debug, Clone)]
+pub struct AggregateAttestation {
+    pub slot: u64,
+    pub committee_index: usize,
+    pub head_hash: String,
+    pub participation: Vec<bool>,
+    pub attesting_stake: u64,
+}
+
+impl AggregateAttestation {
+    pub fn summary(&self) -> String {
+        let bits: String = self
+            .participation
+            .iter()
+            .map(|attested| if *attested { '1' } else { '0' })
+            .collect();
+        format!("{}:{}:{}:{}", self.slot, self.committee_index, self.head_hash, bits)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AttestationDuty {
+    pub assigned: u64,
+    pub attested: u64,
+}
+
+impl Blockchain {
+    // The validators with stake, permuted with the epoch seed.
+    fn shuffle_validators(&self) -> Vec<String> {
+        let mut validators: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|(_, stake)| **stake > 0)
+            .map(|(name, _)| name.clone())
+            .collect();
+        validators.sort();
+        let count = validators.len();
+        (0..count)
+            .map(|i| validators[shuffled_index(i, count, &self.epoch_seed)].clone())
+            .collect()
+    }
+
+    // The epoch's shuffled validators split into committees for `slot`. The shuffle is computed
+    // once per epoch, so committees stay fixed for the whole epoch.
+    pub fn committees_for_slot(&self, slot: u64) -> Vec<Vec<String>> {
+        let Some(shuffled) = &self.epoch_shuffle else {
+            return vec![];
+        };
+        let count = shuffled.len();
+        if count == 0 {
+            return vec![];
+        }
+
+        let slots_per_epoch = self.clock.slots_per_epoch as usize;
+        let committees_per_slot = (count / slots_per_epoch / TARGET_COMMITTEE_SIZE).max(1);
+        let total_committees = committees_per_slot * slots_per_epoch;
+
+        let slot_offset = (slot % self.clock.slots_per_epoch) as usize * committees_per_slot;
+        (slot_offset..slot_offset + committees_per_slot)
+            .map(|committee| {
+                let start = count * committee / total_committees;
+                let end = count * (committee + 1) / total_committees;
+                shuffled[start..end].to_vec()
+            })
+            .filter(|committee| !committee.is_empty())
+            .collect()
+    }
+
+    // Online committee members attest to the current head; each committee's votes are aggregated
+    // and wait in `pending_attestations` for the next block.
+    pub fn attest(&mut self, slot: u64) {
+        if self.epoch_shuffle.is_none() {
+            self.epoch_shuffle = Some(self.shuffle_validators());
+        }
+        let head_hash = self.last_hash();
+        for (committee_index, committee) in self.committees_for_slot(slot).into_iter().enumerate() {
+            let participation: Vec<bool> = committee
+                .iter()
+                .map(|member| !self.offline.contains(member))
+                .collect();
+            let mut attesting_stake = 0;
+            for (member, attested) in committee.iter().zip(&participation) {
+                let duty = self.attestation_duties.entry(member.clone()).or_default();
+                duty.assigned += 1;
+                if *attested {
+                    duty.attested += 1;
+                    attesting_stake += self.nodes.get(member).copied().unwrap_or(0);
+                }
+            }
+            self.pending_attestations.push(AggregateAttestation {
+                slot,
+                committee_index,
+                head_hash: head_hash.clone(),
+                participation,
+                attesting_stake,
+            });
+        }
+    }
+
+    // Re-derives the committee shuffle for the next epoch from the current head.
+    pub fn update_epoch_seed(&mut self, epoch: u64) {
+        self.epoch_seed = sha256(&[self.last_hash().as_bytes(), &epoch.to_le_bytes()]);
+        self.epoch_shuffle = Some(self.shuffle_validators());
+    }
+
+    pub fn participation_report(&self) {
+        let mut per_slot: HashMap<u64, (usize, usize, u64)> = HashMap::new();
+        for block in &self.chain {
+            for aggregate in &block.attestations {
+                let entry = per_slot.entry(aggregate.slot).or_default();
+                entry.0 += aggregate.participation.iter().filter(|attested| **attested).count();
+                entry.1 += aggregate.participation.len();
+                entry.2 += aggregate.attesting_stake;
+            }
+        }
+        let mut slots: Vec<_> = per_slot.into_iter().collect();
+        slots.sort();
+        for (slot, (attested, size, stake)) in slots {
+            println!(
+                "  slot {} attestations included: {}/{} validators, {} stake",
+                slot, attested, size, stake
+            );
+        }
+
+        let mut names: Vec<_> = self.attestation_duties.keys().collect();
+        names.sort();
+        for name in names {
+            let duty = &self.attestation_duties[name];
+            println!(
+                "  {}: attested {}/{} duties ({:.0}%)",
+                name,
+                duty.attested,
+                duty.assigned,
+                duty.attested as f64 * 100.0 / duty.assigned as f64
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    fn blockchain(validators: usize) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for i in 0..validators {
+            blockchain.register_node(Node::new(&format!("validator-{}", i), 10 + i as u64));
+        }
+        blockchain
+    }
+
+    #[test]
+    fn shuffle_is_a_permutation() {
+        let seed = sha256(&[b"seed"]);
+        for count in [1, 7, 100] {
+            let mut positions: Vec<_> = (0..count).map(|i| shuffled_index(i, count, &seed)).collect();
+            positions.sort();
+            assert_eq!(positions, (0..count).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn shuffle_is_stable_for_a_seed() {
+        let (seed, other) = (sha256(&[b"seed"]), sha256(&[b"other"]));
+        let order = |seed: &[u8; 32]| (0..50).map(|i| shuffled_index(i, 50, seed)).collect::<Vec<_>>();
+        assert_eq!(order(&seed), order(&seed));
+        assert_ne!(order(&seed), order(&other));
+    }
+
+    #[test]
+    fn every_validator_sits_on_one_committee_per_epoch() {
+        let mut blockchain = blockchain(40);
+        blockchain.update_epoch_seed(1);
+        let mut members: Vec<String> = (0..blockchain.clock.slots_per_epoch)
+            .flat_map(|slot| blockchain.committees_for_slot(slot).into_iter().flatten())
+            .collect();
+        members.sort();
+        let mut validators: Vec<_> = blockchain.nodes.keys().cloned().collect();
+        validators.sort();
+        assert_eq!(members, validators);
+    }
+
+    #[test]
+    fn attestations_aggregate_online_members() {
+        let mut blockchain = blockchain(40);
+        blockchain.update_epoch_seed(1);
+        let committee = blockchain.committees_for_slot(3).remove(0);
+        blockchain.set_online(&committee[0], false);
+        blockchain.attest(3);
+
+        let aggregate = &blockchain.pending_attestations[0];
+        assert_eq!(aggregate.participation.len(), committee.len());
+        assert!(!aggregate.participation[0]);
+        assert!(aggregate.participation[1..].iter().all(|attested| *attested));
+        let online_stake: u64 = committee[1..].iter().map(|member| blockchain.nodes[member]).sum();
+        assert_eq!(aggregate.attesting_stake, online_stake);
+        assert_eq!(blockchain.attestation_duties[&committee[0]].attested, 0);
+    }
+}
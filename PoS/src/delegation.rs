@@ -0,0 +1,227 @@
+use super::Blockchain;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum DelegationError {
+    UnknownValidator(String),
+    InsufficientBalance { available: u64, requested: u64 },
+    InsufficientDelegation { delegated: u64, requested: u64 },
+    RedelegationCooldown { until_slot: u64 },
+}
+
+impl fmt::Display for DelegationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DelegationError::UnknownValidator(name) => write!(f, "{} is not a registered validator", name),
+            DelegationError::InsufficientBalance { available, requested } => {
+                write!(f, "requested {} but only {} is available", requested, available)
+            }
+            DelegationError::InsufficientDelegation { delegated, requested } => {
+                write!(f, "requested {} but only {} is delegated", requested, delegated)
+            }
+            DelegationError::RedelegationCooldown { until_slot } => {
+                write!(f, "redelegation is on cooldown until slot {}", until_slot)
+            }
+        }
+    }
+}
+
+impl Blockchain {
+    pub fn credit_balance(&mut self, holder: &str, amount: u64) {
+        *self.balances.entry(holder.to_string()).or_insert(0) += amount;
+    }
+
+    pub fn set_commission(&mut self, validator: &str, percent: u64) {
+        self.commissions.insert(validator.to_string(), percent.min(100));
+    }
+
+    fn delegated_to(&self, validator: &str) -> u64 {
+        self.delegations
+            .iter()
+            .filter(|((_, v), _)| v == validator)
+            .map(|(_, amount)| amount)
+            .sum()
+    }
+
+    pub fn self_stake(&self, validator: &str) -> u64 {
+        self.nodes.get(validator).copied().unwrap_or(0) - self.delegated_to(validator)
+    }
+
+    // Bonds `amount` of the delegator's balance to `validator`, adding it to the validator's weight.
+    pub fn delegate(&mut self, delegator: &str, validator: &str, amount: u64) -> Result<(), DelegationError> {
+        if !self.nodes.contains_key(validator) {
+            return Err(DelegationError::UnknownValidator(validator.to_string()));
+        }
+        let available = self.balances.get(delegator).copied().unwrap_or(0);
+        if available < amount {
+            return Err(DelegationError::InsufficientBalance {
+                available,
+                requested: amount,
+            });
+        }
+
+        self.balances.insert(delegator.to_string(), available - amount);
+        *self
+            .delegations
+            .entry((delegator.to_string(), validator.to_string()))
+            .or_insert(0) += amount;
+        *self.nodes.get_mut(validator).unwrap() += amount;
+        self.sampler = None;
+        Ok(())
+    }
+
+    // Moves bonded stake between validators without unbonding. A delegator that redelegates
+    // cannot do so again until `redelegation_cooldown` slots have passed.
+    pub fn redelegate(&mut self, delegator: &str, from: &str, to: &str, amount: u64) -> Result<(), DelegationError> {
+        if !self.nodes.contains_key(to) {
+            return Err(DelegationError::UnknownValidator(to.to_string()));
+        }
+        let now = self.clock.current_slot();
+        if let Some(&until_slot) = self.redelegation_cooldowns.get(delegator) {
+            if now < until_slot {
+                return Err(DelegationError::RedelegationCooldown { until_slot });
+            }
+        }
+        let key = (delegator.to_string(), from.to_string());
+        let delegated = self.delegations.get(&key).copied().unwrap_or(0);
+        if delegated < amount {
+            return Err(DelegationError::InsufficientDelegation {
+                delegated,
+                requested: amount,
+            });
+        }
+
+        if delegated == amount {
+            self.delegations.remove(&key);
+        } else {
+            self.delegations.insert(key, delegated - amount);
+        }
+        *self.nodes.get_mut(from).unwrap() -= amount;
+        *self
+            .delegations
+            .entry((delegator.to_string(), to.to_string()))
+            .or_insert(0) += amount;
+        *self.nodes.get_mut(to).unwrap() += amount;
+        self.redelegation_cooldowns
+            .insert(delegator.to_string(), now + self.redelegation_cooldown);
+        self.sampler = None;
+        Ok(())
+    }
+
+    // Pays the validator its commission, then splits the rest pro-rata over its own stake and
+    // its delegators' stakes. Rounding dust goes to the validator.
+    pub fn distribute_reward(&mut self, validator: &str, reward: u64) {
+        let total = self.nodes.get(validator).copied().unwrap_or(0);
+        if total == 0 {
+            return;
+        }
+        let commission = reward * self.commissions.get(validator).copied().unwrap_or(0) / 100;
+        let shared = reward - commission;
+
+        let shares: Vec<_> = self
+            .delegations
+            .iter()
+            .filter(|((_, v), _)| v == validator)
+            .map(|((delegator, _), amount)| (delegator.clone(), shared * amount / total))
+            .collect();
+        let paid_to_delegators: u64 = shares.iter().map(|(_, share)| share).sum();
+        for (delegator, share) in shares {
+            self.credit_balance(&delegator, share);
+        }
+        self.credit_balance(validator, reward - paid_to_delegators);
+    }
+
+    pub fn delegation_report(&self) {
+        let mut validators: Vec<_> = self.nodes.keys().collect();
+        validators.sort();
+        for validator in validators {
+            println!(
+                "  {}: total stake {}, self stake {}, commission {}%",
+                validator,
+                self.nodes[validator],
+                self.self_stake(validator),
+                self.commissions.get(validator).copied().unwrap_or(0)
+            );
+            let mut delegators: Vec<_> = self
+                .delegations
+                .iter()
+                .filter(|((_, v), _)| v == validator)
+                .collect();
+            delegators.sort();
+            for ((delegator, _), amount) in delegators {
+                println!("    delegated by {}: {}", delegator, amount);
+            }
+        }
+        let mut balances: Vec<_> = self.balances.iter().collect();
+        balances.sort();
+        println!("  liquid balances: {:?}", balances);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    // Alice holds 50 of her own, Dave delegates 30 and Erin 20: 100 in total.
+    fn blockchain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for node in [Node::new("Alice", 50), Node::new("Bob", 40)] {
+            blockchain.register_node(node);
+        }
+        for (delegator, amount) in [("Dave", 30), ("Erin", 20)] {
+            blockchain.credit_balance(delegator, amount);
+            blockchain.delegate(delegator, "Alice", amount).unwrap();
+        }
+        blockchain
+    }
+
+    fn balance(blockchain: &Blockchain, holder: &str) -> u64 {
+        blockchain.balances.get(holder).copied().unwrap_or(0)
+    }
+
+    #[test]
+    fn reward_pays_commission_then_splits_pro_rata() {
+        let mut blockchain = blockchain();
+        blockchain.set_commission("Alice", 10);
+        blockchain.distribute_reward("Alice", 33);
+        // 3 commission; the remaining 30 is split 15/9/6 by stake.
+        assert_eq!(balance(&blockchain, "Dave"), 9);
+        assert_eq!(balance(&blockchain, "Erin"), 6);
+        assert_eq!(balance(&blockchain, "Alice"), 18);
+    }
+
+    #[test]
+    fn rounding_dust_goes_to_the_validator() {
+        let mut blockchain = blockchain();
+        blockchain.distribute_reward("Alice", 7);
+        // Shares of 3.5, 2.1 and 1.4 round down for delegators; Alice keeps the remainder.
+        assert_eq!(balance(&blockchain, "Dave"), 2);
+        assert_eq!(balance(&blockchain, "Erin"), 1);
+        assert_eq!(balance(&blockchain, "Alice"), 4);
+    }
+
+    #[test]
+    fn slash_cuts_own_and_delegated_stake_proportionally() {
+        let mut blockchain = blockchain();
+        assert_eq!(blockchain.slash("Alice", 50), 50);
+        assert_eq!(blockchain.nodes["Alice"], 50);
+        assert_eq!(blockchain.self_stake("Alice"), 25);
+        assert_eq!(blockchain.delegations[&("Dave".to_string(), "Alice".to_string())], 15);
+        assert_eq!(blockchain.delegations[&("Erin".to_string(), "Alice".to_string())], 10);
+    }
+
+    #[test]
+    fn redelegation_is_on_cooldown() {
+        let mut blockchain = blockchain();
+        blockchain.redelegate("Dave", "Alice", "Bob", 10).unwrap();
+        assert_eq!((blockchain.nodes["Alice"], blockchain.nodes["Bob"]), (90, 50));
+        assert!(matches!(
+            blockchain.redelegate("Dave", "Bob", "Alice", 10),
+            Err(DelegationError::RedelegationCooldown { until_slot: 16 })
+        ));
+
+        blockchain.clock.advance_to(16);
+        assert!(blockchain.redelegate("Dave", "Bob", "Alice", 10).is_ok());
+    }
+}
@@ -0,0 +1,200 @@
+use super::{Blockchain, Node};
+use crate::sampling::SelectionStrategy;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+
+pub struct FairnessReport {
+    label: String,
+    draws: usize,
+    chi_square: f64,
+    p_value: f64,
+    ks_statistic: f64,
+    ks_critical: f64,
+}
+
+impl FairnessReport {
+    pub fn passed(&self, alpha: f64) -> bool {
+        self.p_value >= alpha && self.ks_statistic <= self.ks_critical
+    }
+
+    pub fn print(&self, alpha: f64) {
+        println!(
+            "{}: {} draws | chi-square {:.2} (p = {:.4}) | KS D = {:.6} (critical {:.6}) | {}",
+            self.label,
+            self.draws,
+            self.chi_square,
+            self.p_value,
+            self.ks_statistic,
+            self.ks_critical,
+            if self.passed(alpha) { "PASS" } else { "FAIL" }
+        );
+    }
+}
+
+// Lanczos approximation of ln(Γ(x)).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000000000190015;
+    for (i, c) in COEFFICIENTS.iter().enumerate() {
+        series += c / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+// Regularized upper incomplete gamma Q(a, x): series for x < a + 1, continued fraction otherwise.
+fn upper_gamma(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let log_prefix = -x + a * x.ln() - ln_gamma(a);
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut n = a;
+        for _ in 0..1000 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        1.0 - sum * log_prefix.exp()
+    } else {
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        log_prefix.exp() * h
+    }
+}
+
+// Runs `draws` selections and compares the observed frequencies with the stake distribution
+// using Pearson's chi-square test and a one-sample Kolmogorov-Smirnov test.
+pub fn check_strategy(
+    stakes: &[Node],
+    strategy: SelectionStrategy,
+    draws: usize,
+    alpha: f64,
+    seed: u64,
+) -> FairnessReport {
+    let mut blockchain = Blockchain::new();
+    for node in stakes {
+        blockchain.register_node(node.clone());
+    }
+    blockchain.selection = strategy;
+    blockchain.refresh_sampler();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for _ in 0..draws {
+        if let Some(name) = blockchain.select_validator_with(&mut rng) {
+            *counts.entry(name).or_insert(0) += 1;
+        }
+    }
+
+    let mut names: Vec<_> = blockchain.nodes.keys().cloned().collect();
+    names.sort();
+    let total_stake: u64 = blockchain.nodes.values().sum();
+
+    let mut chi_square = 0.0;
+    let mut ks_statistic: f64 = 0.0;
+    let (mut observed_cdf, mut expected_cdf) = (0.0, 0.0);
+    for name in &names {
+        let probability = blockchain.nodes[name] as f64 / total_stake as f64;
+        let observed = *counts.get(name).unwrap_or(&0) as f64;
+        let expected = probability * draws as f64;
+        chi_square += (observed - expected).powi(2) / expected;
+
+        observed_cdf += observed / draws as f64;
+        expected_cdf += probability;
+        ks_statistic = ks_statistic.max((observed_cdf - expected_cdf).abs());
+    }
+
+    let degrees_of_freedom = (names.len() - 1) as f64;
+    FairnessReport {
+        label: format!("{:?} over {} validators", strategy, names.len()),
+        draws,
+        chi_square,
+        p_value: upper_gamma(degrees_of_freedom / 2.0, chi_square / 2.0),
+        ks_statistic,
+        ks_critical: (-0.5 * (alpha / 2.0).ln()).sqrt() / (draws as f64).sqrt(),
+    }
+}
+
+// A small and a large, skewed stake distribution.
+fn stake_distributions() -> [Vec<Node>; 2] {
+    let small = vec![Node::new("Alice", 50), Node::new("Bob", 30), Node::new("Charlie", 20)];
+    let large: Vec<_> = (1..=200)
+        .map(|i| Node::new(&format!("validator-{}", i), (i * i) % 997 + 1))
+        .collect();
+    [small, large]
+}
+
+// Checks every selection strategy against both stake distributions.
+// Returns false if any of them is biased at significance level `alpha`.
+pub fn run_harness(draws: usize, alpha: f64) -> bool {
+    let distributions = stake_distributions();
+    let mut all_passed = true;
+    for (seed, strategy) in SelectionStrategy::ALL.into_iter().enumerate() {
+        for stakes in &distributions {
+            let report = check_strategy(stakes, strategy, draws, alpha, seed as u64);
+            report.print(alpha);
+            all_passed &= report.passed(alpha);
+        }
+    }
+    all_passed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fewer draws than the harness, and a stricter level so the linear scan, whose order follows
+    // the stake map's hashing, does not fail by chance.
+    #[test]
+    fn every_strategy_is_unbiased() {
+        let alpha = 1e-6;
+        for (seed, strategy) in SelectionStrategy::ALL.into_iter().enumerate() {
+            for stakes in &stake_distributions() {
+                let report = check_strategy(stakes, strategy, 100_000, alpha, seed as u64);
+                assert!(report.passed(alpha), "{} failed", report.label);
+            }
+        }
+    }
+
+    // The full million-draw run; slow in debug builds.
+    #[test]
+    #[ignore]
+    fn full_harness_passes() {
+        assert!(run_harness(1_000_000, 0.001));
+    }
+}
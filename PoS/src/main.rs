@@ -1,5 +1,22 @@
+mod attacks;
+mod checkpoint;
+mod committees;
+mod delegation;
+mod fairness;
+mod praos;
+mod sampling;
+mod slots;
+mod validator_queue;
+
+use checkpoint::Checkpoint;
+use committees::{AggregateAttestation, AttestationDuty};
+use praos::{LeaderElection, LeaderProof};
 use rand::Rng;
-use std::collections::HashMap;
+use sampling::{AliasTable, SelectionStrategy};
+use schnorrkel::{Keypair, PublicKey};
+use sha2::{Digest, Sha256};
+use slots::{Liveness, SlotClock, SlotRecord};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
@@ -7,8 +24,56 @@ use std::time::Duration;
 #[derive(Debug, Clone)]
 struct Block {
     id: u64,
+    slot: u64,
     validator: String,
     transactions: Vec<String>,
+    attestations: Vec<AggregateAttestation>,
+    leader_proof: Option<LeaderProof>,
+    previous_hash: String,
+    hash: String,
+}
+
+impl Block {
+    fn new(id: u64, slot: u64, previous_hash: String, transactions: Vec<String>, validator: String) -> Self {
+        let mut block = Block {
+            id,
+            slot,
+            validator,
+            transactions,
+            attestations: vec![],
+            leader_proof: None,
+            previous_hash,
+            hash: String::new(),
+        };
+        block.hash = block.calculate_hash();
+        block
+    }
+
+    fn with_attestations(mut self, attestations: Vec<AggregateAttestation>) -> Self {
+        self.attestations = attestations;
+        self.hash = self.calculate_hash();
+        self
+    }
+
+    fn calculate_hash(&self) -> String {
+        let attestations: Vec<_> = self.attestations.iter().map(|a| a.summary()).collect();
+        let leader_proof = self.leader_proof.as_ref().map_or(String::new(), |claim| {
+            claim.output.iter().map(|byte| format!("{:02x}", byte)).collect()
+        });
+        let data = format!(
+            "{}{}{}{}{}{}{}",
+            self.id,
+            self.slot,
+            self.previous_hash,
+            self.validator,
+            self.transactions.join(""),
+            attestations.join(""),
+            leader_proof
+        );
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        format!("{:x}", hasher.finalize())
+    }
 }
 
 #[derive(Debug, Clone)] // Added Clone trait
@@ -30,6 +95,37 @@ struct Blockchain {
     chain: Vec<Block>,
     pending_transactions: Vec<String>,
     nodes: HashMap<String, u64>,
+    selection: SelectionStrategy,
+    sampler: Option<AliasTable>,
+    clock: SlotClock,
+    slots: Vec<SlotRecord>,
+    offline: HashSet<String>,
+    liveness: HashMap<String, Liveness>,
+    inactivity_threshold: u64,
+    inactivity_penalty: u64,
+    epoch_seed: [u8; 32],
+    // The epoch's validators in shuffled order, rebuilt when the seed changes.
+    epoch_shuffle: Option<Vec<String>>,
+    pending_attestations: Vec<AggregateAttestation>,
+    attestation_duties: HashMap<String, AttestationDuty>,
+    balances: HashMap<String, u64>,
+    delegations: HashMap<(String, String), u64>,
+    commissions: HashMap<String, u64>,
+    redelegation_cooldowns: HashMap<String, u64>,
+    redelegation_cooldown: u64,
+    block_reward: u64,
+    checkpoint: Option<Checkpoint>,
+    weak_subjectivity_period: u64,
+    leader_election: LeaderElection,
+    vrf_keys: HashMap<String, Keypair>,
+    vrf_public_keys: HashMap<String, PublicKey>,
+    forks: Vec<Vec<Block>>,
+    orphaned_forks: usize,
+    activation_queue: VecDeque<Node>,
+    exit_queue: VecDeque<String>,
+    validator_history: BTreeMap<u64, HashMap<String, u64>>,
+    min_churn_limit: usize,
+    churn_limit_quotient: usize,
 }
 
 impl Blockchain {
@@ -38,6 +134,36 @@ impl Blockchain {
             chain: vec![],
             pending_transactions: vec![],
             nodes: HashMap::new(),
+            selection: SelectionStrategy::Alias,
+            sampler: None,
+            clock: SlotClock::new(Duration::from_secs(3), 8),
+            slots: vec![],
+            offline: HashSet::new(),
+            liveness: HashMap::new(),
+            inactivity_threshold: 4,
+            inactivity_penalty: 5,
+            epoch_seed: [0; 32],
+            epoch_shuffle: None,
+            pending_attestations: vec![],
+            attestation_duties: HashMap::new(),
+            balances: HashMap::new(),
+            delegations: HashMap::new(),
+            commissions: HashMap::new(),
+            redelegation_cooldowns: HashMap::new(),
+            redelegation_cooldown: 16,
+            block_reward: 10,
+            checkpoint: None,
+            weak_subjectivity_period: 256,
+            leader_election: LeaderElection::Public,
+            vrf_keys: HashMap::new(),
+            vrf_public_keys: HashMap::new(),
+            forks: vec![],
+            orphaned_forks: 0,
+            activation_queue: VecDeque::new(),
+            exit_queue: VecDeque::new(),
+            validator_history: BTreeMap::new(),
+            min_churn_limit: 1,
+            churn_limit_quotient: 4,
         }
     }
 
@@ -45,17 +171,28 @@ impl Blockchain {
         self.pending_transactions.push(transaction);
     }
 
-    fn register_node(&mut self, node: Node) {
-        self.nodes.insert(node.name, node.stake);
+    // Rebuilds the alias table after stake changes; a no-op for the linear scan.
+    fn refresh_sampler(&mut self) {
+        if self.selection == SelectionStrategy::Alias && self.sampler.is_none() {
+            self.sampler = AliasTable::new(&self.nodes);
+        }
     }
 
     fn select_validator(&self) -> Option<String> {
+        self.select_validator_with(&mut rand::thread_rng())
+    }
+
+    // Uses the alias table when it is up to date and falls back to the linear scan otherwise.
+    fn select_validator_with<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<String> {
+        if let (SelectionStrategy::Alias, Some(sampler)) = (self.selection, &self.sampler) {
+            return Some(sampler.sample(rng).to_string());
+        }
+
         let total_stake: u64 = self.nodes.values().sum();
         if total_stake == 0 {
             return None;
         }
 
-        let mut rng = rand::thread_rng();
         let mut random_weight = rng.gen_range(0..total_stake);
 
         for (name, stake) in &self.nodes {
@@ -68,30 +205,70 @@ impl Blockchain {
         None
     }
 
-    fn mine_block(&mut self) {
-        if let Some(validator) = self.select_validator() {
-            let block = Block {
-                id: self.chain.len() as u64 + 1,
-                validator: validator.clone(),
-                transactions: self.pending_transactions.clone(),
-            };
+    fn last_hash(&self) -> String {
+        self.chain
+            .last()
+            .map(|block| block.hash.clone())
+            .unwrap_or_else(|| String::from("0"))
+    }
+
+    fn append_block(&mut self, validator: String) {
+        let block = Block::new(
+            self.chain.last().map_or(1, |block| block.id + 1),
+            self.clock.current_slot(),
+            self.last_hash(),
+            self.pending_transactions.clone(),
+            validator,
+        )
+        .with_attestations(std::mem::take(&mut self.pending_attestations));
+        self.chain.push(block);
+        self.pending_transactions.clear();
+    }
 
-            self.chain.push(block);
-            self.pending_transactions.clear();
-            println!("Block mined by {}", validator);
-        } else {
-            println!("No validator selected. Check stakes!");
+    // Slashes `fraction` percent of the validator's own stake and of every delegation to it,
+    // and returns the amount burned.
+    fn slash(&mut self, validator: &str, fraction: u64) -> u64 {
+        if !self.nodes.contains_key(validator) {
+            return 0;
         }
+        let mut penalty = self.self_stake(validator) * fraction / 100;
+        for ((_, delegate), amount) in self.delegations.iter_mut() {
+            if delegate == validator {
+                let cut = *amount * fraction / 100;
+                *amount -= cut;
+                penalty += cut;
+            }
+        }
+        *self.nodes.get_mut(validator).unwrap() -= penalty;
+        self.sampler = None;
+        penalty
     }
 
     fn display_chain(&self) {
         for block in &self.chain {
-            println!("Block ID: {} | Validator: {} | Transactions: {:?}", block.id, block.validator, block.transactions);
+            println!(
+                "Block ID: {} | Slot: {} | Validator: {} | Transactions: {:?}",
+                block.id, block.slot, block.validator, block.transactions
+            );
         }
     }
 }
 
 fn main() {
+    match std::env::args().nth(1).as_deref() {
+        Some("bench-sampling") => {
+            sampling::benchmark(100_000, 20_000);
+            return;
+        }
+        Some("fairness") => {
+            if !fairness::run_harness(1_000_000, 0.001) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        _ => {}
+    }
+
     let blockchain = Arc::new(Mutex::new(Blockchain::new()));
 
     let nodes = vec![
@@ -104,20 +281,139 @@ fn main() {
         blockchain.lock().unwrap().register_node(node.clone());
     }
 
+    for outcome in [
+        attacks::nothing_at_stake(false),
+        attacks::nothing_at_stake(true),
+        attacks::long_range(false),
+        attacks::long_range(true),
+    ] {
+        outcome.report();
+    }
+
+    // Praos: private VRF leader election, where slots can have zero, one or several leaders.
+    {
+        let mut praos = Blockchain::new();
+        for node in &nodes {
+            praos.register_node(node.clone());
+        }
+        praos.enable_praos();
+        for _ in 0..16 {
+            praos.next_slot();
+        }
+        praos.praos_report();
+
+        // A peer checks every leader proof against the nonce and stake of the block's epoch.
+        match praos.adopt_fork(praos.chain.clone()) {
+            Ok(_) => println!("Peer chain of {} blocks verified", praos.chain.len()),
+            Err(err) => println!("Peer chain rejected: {}", err),
+        }
+        if let Some(mut forged) = praos.chain.last().cloned() {
+            let mut fork = praos.chain.clone();
+            fork.pop();
+            forged.validator = "Mallory".to_string();
+            forged.hash = forged.calculate_hash();
+            fork.push(forged);
+            if let Err(err) = praos.adopt_fork(fork) {
+                println!("Peer chain rejected: {}", err);
+            }
+        }
+    }
+
+    // After genesis, validators join and leave through queues limited by the per-epoch churn.
+    {
+        let mut churn = Blockchain::new();
+        for node in &nodes {
+            churn.register_node(node.clone());
+        }
+        churn.next_slot();
+        for node in [Node::new("Dave", 15), Node::new("Erin", 15), Node::new("Frank", 15)] {
+            churn.register_node(node);
+        }
+        churn.request_exit("Charlie");
+        for epoch in 0..3 {
+            churn.process_validator_queues(epoch);
+        }
+        churn.validator_queue_report();
+        if let Some(validators) = churn.validator_set_at(1) {
+            println!("Validator set at epoch 1: {:?}", validators);
+        }
+    }
+
+    {
+        let mut blockchain = blockchain.lock().unwrap();
+        blockchain.set_commission("Alice", 10);
+        blockchain.set_commission("Charlie", 5);
+        blockchain.credit_balance("Dave", 40);
+        blockchain.credit_balance("Erin", 25);
+        for (delegator, validator, amount) in [("Dave", "Alice", 40), ("Erin", "Charlie", 25)] {
+            if let Err(err) = blockchain.delegate(delegator, validator, amount) {
+                println!("Delegation from {} to {} failed: {}", delegator, validator, err);
+            }
+        }
+        // The second redelegation falls inside Erin's cooldown and is rejected.
+        for (from, to) in [("Charlie", "Bob"), ("Bob", "Alice")] {
+            match blockchain.redelegate("Erin", from, to, 10) {
+                Ok(()) => println!("Erin redelegated 10 from {} to {}", from, to),
+                Err(err) => println!("Erin could not redelegate from {} to {}: {}", from, to, err),
+            }
+        }
+    }
+
+    // Charlie goes offline and misses every slot it is selected for.
+    blockchain.lock().unwrap().set_online("Charlie", false);
+    let slot_duration = blockchain.lock().unwrap().clock.slot_duration;
+
     let blockchain_clone = Arc::clone(&blockchain);
     let miner_thread = thread::spawn(move || {
         loop {
-            thread::sleep(Duration::from_secs(3));
+            thread::sleep(slot_duration);
             let mut blockchain = blockchain_clone.lock().unwrap();
-            blockchain.mine_block();
+            blockchain.next_slot();
         }
     });
 
     blockchain.lock().unwrap().add_transaction("Alice -> Bob: 10 coins".to_string());
     blockchain.lock().unwrap().add_transaction("Bob -> Charlie: 5 coins".to_string());
 
-    thread::sleep(Duration::from_secs(10));
+    // Wait for the first block before checkpointing; offline proposers can leave early slots empty.
+    thread::sleep(Duration::from_secs(5));
+    let checkpoint = loop {
+        if let Some(checkpoint) = blockchain.lock().unwrap().latest_checkpoint() {
+            break checkpoint;
+        }
+        thread::sleep(slot_duration);
+    };
+    thread::sleep(Duration::from_secs(5));
     blockchain.lock().unwrap().display_chain();
+    blockchain.lock().unwrap().liveness_report();
+    blockchain.lock().unwrap().participation_report();
+    blockchain.lock().unwrap().delegation_report();
+
+    // A new node joins from the earlier checkpoint instead of replaying the chain from genesis.
+    {
+        let blockchain = blockchain.lock().unwrap();
+        let current_slot = blockchain.clock.current_slot();
+        let stale_slot = current_slot + blockchain.weak_subjectivity_period + 1;
+        if let Err(err) = Blockchain::from_checkpoint(checkpoint.clone(), stale_slot) {
+            println!("Checkpoint sync refused: {}", err);
+        }
+        match Blockchain::from_checkpoint(checkpoint, current_slot) {
+            Ok(mut node) => {
+                let forged = attacks::forged_chain(blockchain.chain.len() + 5);
+                if let Err(err) = node.sync_from(&forged) {
+                    println!("Checkpoint sync rejected forged chain: {}", err);
+                }
+                match node.sync_from(&blockchain.chain) {
+                    Ok(synced) => println!(
+                        "Checkpoint sync: started at block {}, synced {} blocks",
+                        node.chain[0].id, synced
+                    ),
+                    Err(err) => println!("Checkpoint sync failed: {}", err),
+                }
+            }
+            Err(err) => println!("Checkpoint sync refused: {}", err),
+        }
+    }
 
     miner_thread.join().unwrap();
 }
@@ -0,0 +1,344 @@
+use super::{Block, Blockchain};
+use crate::slots::SlotRecord;
+use rand::seq::SliceRandom;
+use rand::{CryptoRng, Rng, RngCore};
+use schnorrkel::vrf::{VRFPreOut, VRFProof};
+use schnorrkel::{signing_context, Keypair};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+// Active slot coefficient f: the probability that a slot has at least one leader.
+const ACTIVE_SLOT_COEFFICIENT: f64 = 0.5;
+const VRF_CONTEXT: &[u8] = b"praos-leader";
+const VRF_OUTPUT_LABEL: &[u8] = b"praos-output";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LeaderElection {
+    // One proposer per slot drawn publicly with `select_validator`.
+    Public,
+    // Every stakeholder privately evaluates a VRF; zero, one or several may lead a slot.
+    Praos,
+}
+
+// VRF output and the proof that lets anyone holding the leader's public key check it.
+// This is schnorrkel's Ristretto VRF: the pre-output is the secret key times a hash of the
+// input, so the output is fixed by key and input alone. Only the proof is randomized, and
+// re-signing cannot produce a different output to grind for a lower value.
+#[derive(Debug, Clone)]
+pub struct LeaderProof {
+    pub output: [u8; 32],
+    pub preout: VRFPreOut,
+    pub proof: VRFProof,
+}
+
+#[derive(Debug)]
+pub enum ForkError {
+    UnknownParent(u64),
+    InvalidSlot(u64),
+    InvalidLeaderProof(u64),
+}
+
+impl fmt::Display for ForkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ForkError::UnknownParent(id) => write!(f, "block {} does not link to its parent", id),
+            ForkError::InvalidSlot(id) => write!(f, "block {} is not in a later, past slot", id),
+            ForkError::InvalidLeaderProof(id) => write!(f, "block {} has no valid leader proof", id),
+        }
+    }
+}
+
+fn vrf_input(nonce: &[u8; 32], slot: u64) -> Vec<u8> {
+    let mut input = nonce.to_vec();
+    input.extend_from_slice(&slot.to_le_bytes());
+    input
+}
+
+fn vrf_evaluate(key: &Keypair, nonce: &[u8; 32], slot: u64) -> LeaderProof {
+    let (inout, proof, _) = key.vrf_sign(signing_context(VRF_CONTEXT).bytes(&vrf_input(nonce, slot)));
+    LeaderProof {
+        output: inout.make_bytes(VRF_OUTPUT_LABEL),
+        preout: inout.to_preout(),
+        proof,
+    }
+}
+
+fn vrf_verify(key: &schnorrkel::PublicKey, nonce: &[u8; 32], slot: u64, claim: &LeaderProof) -> bool {
+    key.vrf_verify(signing_context(VRF_CONTEXT).bytes(&vrf_input(nonce, slot)), &claim.preout, &claim.proof)
+        .is_ok_and(|(inout, _)| inout.make_bytes::<[u8; 32]>(VRF_OUTPUT_LABEL) == claim.output)
+}
+
+// φ(α) = 1 - (1 - f)^α: independent of how stake is split between keys.
+fn leader_threshold(relative_stake: f64) -> f64 {
+    1.0 - (1.0 - ACTIVE_SLOT_COEFFICIENT).powf(relative_stake)
+}
+
+fn output_fraction(output: &[u8; 32]) -> f64 {
+    u64::from_be_bytes(output[..8].try_into().unwrap()) as f64 / u64::MAX as f64
+}
+
+impl Blockchain {
+    // Switches to Praos leader election and gives every stakeholder a VRF key pair.
+    pub fn enable_praos(&mut self) {
+        self.enable_praos_with(&mut rand::thread_rng());
+    }
+
+    pub fn enable_praos_with<R: RngCore + CryptoRng>(&mut self, rng: &mut R) {
+        let mut names: Vec<_> = self.nodes.keys().cloned().collect();
+        names.sort();
+        for name in names {
+            if !self.vrf_keys.contains_key(&name) {
+                let key = Keypair::generate_with(&mut *rng);
+                self.vrf_public_keys.insert(name.clone(), key.public);
+                self.vrf_keys.insert(name, key);
+            }
+        }
+        self.leader_election = LeaderElection::Praos;
+        self.forks = vec![self.chain.clone()];
+    }
+
+    // Stake share from the validator set fixed at the start of `epoch`, so a block is always
+    // judged against the distribution that was in force when it was made.
+    fn relative_stake(&self, name: &str, epoch: u64) -> f64 {
+        let Some(validators) = self.validator_set_at(epoch) else {
+            return 0.0;
+        };
+        let total: u64 = validators.values().sum();
+        if total == 0 {
+            return 0.0;
+        }
+        validators.get(name).copied().unwrap_or(0) as f64 / total as f64
+    }
+
+    // The epoch nonce of a fork: derived from its last block before the epoch, so every node
+    // holding the same fork computes the same nonce.
+    fn epoch_nonce(&self, fork: &[Block], epoch: u64) -> [u8; 32] {
+        if epoch == 0 {
+            return [0; 32];
+        }
+        let anchor = fork
+            .iter()
+            .rev()
+            .find(|block| self.clock.epoch_of(block.slot) < epoch)
+            .map_or_else(|| String::from("0"), |block| block.hash.clone());
+        let mut hasher = Sha256::new();
+        hasher.update(anchor.as_bytes());
+        hasher.update(epoch.to_le_bytes());
+        hasher.finalize().into()
+    }
+
+    // Checks `block` as the next block after `parent_chain`, using the nonce and stake of the
+    // block's own epoch.
+    pub fn verify_leader(&self, parent_chain: &[Block], block: &Block) -> bool {
+        let (Some(claim), Some(key)) = (&block.leader_proof, self.vrf_public_keys.get(&block.validator)) else {
+            return false;
+        };
+        let epoch = self.clock.epoch_of(block.slot);
+        vrf_verify(key, &self.epoch_nonce(parent_chain, epoch), block.slot, claim)
+            && output_fraction(&claim.output) < leader_threshold(self.relative_stake(&block.validator, epoch))
+    }
+
+    // Validates a chain received from a peer block by block and adds it to the competing forks.
+    // Returns whether it became our best chain.
+    pub fn adopt_fork(&mut self, fork: Vec<Block>) -> Result<bool, ForkError> {
+        for (index, block) in fork.iter().enumerate() {
+            let parent_chain = &fork[..index];
+            let parent = parent_chain.last();
+            let previous_hash = parent.map_or_else(|| String::from("0"), |parent| parent.hash.clone());
+            if block.previous_hash != previous_hash || block.hash != block.calculate_hash() {
+                return Err(ForkError::UnknownParent(block.id));
+            }
+            if parent.is_some_and(|parent| block.slot <= parent.slot) || block.slot > self.clock.current_slot() {
+                return Err(ForkError::InvalidSlot(block.id));
+            }
+            if !self.verify_leader(parent_chain, block) {
+                return Err(ForkError::InvalidLeaderProof(block.id));
+            }
+        }
+
+        let tip = fork.last().map(|block| block.hash.clone());
+        self.add_forks(vec![fork]);
+        Ok(self.chain.last().map(|block| block.hash.clone()) == tip)
+    }
+
+    // Replaces the forks that `extended` builds on, drops forks that fall behind the longest
+    // one (counting them as orphaned), and follows the best remaining fork.
+    fn add_forks(&mut self, extended: Vec<Vec<Block>>) {
+        let known: Vec<_> = self
+            .forks
+            .iter()
+            .filter_map(|fork| fork.last().map(|tip| tip.hash.clone()))
+            .collect();
+        let extended: Vec<_> = extended
+            .into_iter()
+            .filter(|fork| fork.last().is_some_and(|tip| !known.contains(&tip.hash)))
+            .collect();
+        let superseded: Vec<_> = extended
+            .iter()
+            .flat_map(|fork| fork.iter().map(|block| block.hash.clone()))
+            .collect();
+        self.forks
+            .retain(|fork| fork.last().is_some_and(|tip| !superseded.contains(&tip.hash)));
+        self.forks.extend(extended);
+        let longest = self.forks.iter().map(|fork| fork.len()).max().unwrap_or(0);
+        let before: usize = self.forks.len();
+        self.forks.retain(|fork| fork.len() == longest);
+        self.orphaned_forks += before - self.forks.len();
+        if let Some(best) = self.best_fork() {
+            self.chain = best.clone();
+        }
+    }
+
+    // Longest chain wins; among equally long chains, the tip with the lowest VRF output.
+    fn best_fork(&self) -> Option<&Vec<Block>> {
+        let tip_output = |fork: &Vec<Block>| {
+            fork.last()
+                .and_then(|block| block.leader_proof.as_ref())
+                .map_or(f64::MAX, |claim| output_fraction(&claim.output))
+        };
+        self.forks.iter().max_by(|a, b| {
+            a.len()
+                .cmp(&b.len())
+                .then(tip_output(b).total_cmp(&tip_output(a)))
+        })
+    }
+
+    // Each online stakeholder picks one of the longest forks it has seen and privately checks
+    // its VRF output for the slot against its threshold; nobody else learns who leads a slot
+    // until the block with the proof arrives.
+    pub fn praos_slot(&mut self) {
+        self.praos_slot_with(&mut rand::thread_rng());
+    }
+
+    pub fn praos_slot_with<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        let slot = self.clock.tick();
+        let epoch = self.clock.epoch_of(slot);
+
+        let longest = self.forks.iter().map(|fork| fork.len()).max().unwrap_or(0);
+        let tips: Vec<_> = self.forks.iter().filter(|fork| fork.len() == longest).cloned().collect();
+        let mut names: Vec<_> = self
+            .vrf_keys
+            .keys()
+            .filter(|name| !self.offline.contains(*name))
+            .cloned()
+            .collect();
+        names.sort();
+
+        let mut leaders = vec![];
+        let mut extended = vec![];
+        for name in names {
+            let mut fork = tips.choose(rng).cloned().unwrap_or_default();
+            let claim = vrf_evaluate(&self.vrf_keys[&name], &self.epoch_nonce(&fork, epoch), slot);
+            if output_fraction(&claim.output) >= leader_threshold(self.relative_stake(&name, epoch)) {
+                continue;
+            }
+            let parent = fork.last();
+            let mut block = Block::new(
+                parent.map_or(1, |block| block.id + 1),
+                slot,
+                parent.map_or_else(|| String::from("0"), |block| block.hash.clone()),
+                self.pending_transactions.clone(),
+                name.clone(),
+            );
+            block.leader_proof = Some(claim);
+            block.hash = block.calculate_hash();
+            if self.verify_leader(&fork, &block) {
+                fork.push(block);
+                extended.push(fork);
+                leaders.push(name);
+            }
+        }
+
+        if !extended.is_empty() {
+            self.pending_transactions.clear();
+            self.add_forks(extended);
+        }
+
+        println!("Slot {}: leaders {:?}, {} competing tips", slot, leaders, self.forks.len());
+        self.slots.push(SlotRecord {
+            slot,
+            proposer: leaders.first().cloned(),
+            block_id: if leaders.is_empty() { None } else { self.chain.last().map(|block| block.id) },
+        });
+        if self.clock.is_epoch_end(slot) {
+            self.process_validator_queues(epoch);
+            self.update_epoch_seed(epoch + 1);
+        }
+    }
+
+    pub fn praos_report(&self) {
+        let empty = self.slots.iter().filter(|record| record.block_id.is_none()).count();
+        println!(
+            "Praos: {} slots, {} without a leader, chain length {}, {} competing tips, {} forks orphaned",
+            self.slots.len(),
+            empty,
+            self.chain.len(),
+            self.forks.len(),
+            self.orphaned_forks
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // Seeded keys and tip choices, so every run produces the same chain.
+    fn praos_chain(slots: usize) -> (Blockchain, StdRng) {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut blockchain = Blockchain::new();
+        for node in [Node::new("Alice", 50), Node::new("Bob", 30), Node::new("Charlie", 20)] {
+            blockchain.register_node(node);
+        }
+        blockchain.enable_praos_with(&mut rng);
+        for _ in 0..slots {
+            blockchain.praos_slot_with(&mut rng);
+        }
+        (blockchain, rng)
+    }
+
+    #[test]
+    fn vrf_output_cannot_be_ground_by_resigning() {
+        let key = Keypair::generate_with(StdRng::seed_from_u64(3));
+        let first = vrf_evaluate(&key, &[7; 32], 3);
+        let second = vrf_evaluate(&key, &[7; 32], 3);
+        assert_eq!(first.output, second.output);
+        assert!(vrf_verify(&key.public, &[7; 32], 3, &second));
+        assert!(!vrf_verify(&key.public, &[7; 32], 4, &first));
+    }
+
+    #[test]
+    fn peer_chain_spanning_epochs_verifies() {
+        let (blockchain, _) = praos_chain(24);
+        let mut peer = Blockchain {
+            chain: vec![],
+            forks: vec![vec![]],
+            ..praos_chain(0).0
+        };
+        peer.vrf_public_keys = blockchain.vrf_public_keys.clone();
+        peer.validator_history = blockchain.validator_history.clone();
+        peer.clock.advance_to(blockchain.clock.current_slot());
+        let epochs = blockchain.chain.last().map(|block| peer.clock.epoch_of(block.slot));
+        assert!(epochs.is_some_and(|epoch| epoch >= 1));
+        assert!(peer.adopt_fork(blockchain.chain.clone()).unwrap());
+    }
+
+    #[test]
+    fn forged_leader_proof_is_rejected() {
+        let (mut blockchain, mut rng) = praos_chain(8);
+        // A slot has no leader about half the time; keep going until there is a block to forge.
+        while blockchain.chain.is_empty() {
+            blockchain.praos_slot_with(&mut rng);
+        }
+        let mut fork = blockchain.chain.clone();
+        let mut forged = fork.last().cloned().unwrap();
+        forged.validator = if forged.validator == "Alice" { "Bob" } else { "Alice" }.to_string();
+        forged.hash = forged.calculate_hash();
+        fork.pop();
+        fork.push(forged);
+        assert!(matches!(blockchain.adopt_fork(fork), Err(ForkError::InvalidLeaderProof(_))));
+    }
+}
@@ -0,0 +1,171 @@
+use super::Blockchain;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionStrategy {
+    // Walk the stake map on every draw: O(n) per block.
+    LinearScan,
+    // Walker/Vose alias table rebuilt when stakes change: O(1) per block.
+    Alias,
+}
+
+impl SelectionStrategy {
+    pub const ALL: [SelectionStrategy; 2] = [SelectionStrategy::LinearScan, SelectionStrategy::Alias];
+}
+
+// Integer alias table. Each bucket holds `total` units split between its own validator
+// (`threshold`) and its alias, so selection probabilities are exactly stake / total.
+#[derive(Debug, Clone)]
+pub struct AliasTable {
+    names: Vec<String>,
+    threshold: Vec<u128>,
+    alias: Vec<usize>,
+    total: u128,
+}
+
+impl AliasTable {
+    pub fn new(stakes: &HashMap<String, u64>) -> Option<Self> {
+        let mut entries: Vec<_> = stakes.iter().filter(|(_, stake)| **stake > 0).collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        if entries.is_empty() {
+            return None;
+        }
+
+        let n = entries.len() as u128;
+        let total: u128 = entries.iter().map(|(_, stake)| **stake as u128).sum();
+        let mut scaled: Vec<u128> = entries.iter().map(|(_, stake)| **stake as u128 * n).collect();
+        let mut threshold = vec![total; entries.len()];
+        let mut alias: Vec<usize> = (0..entries.len()).collect();
+
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) =
+            (0..entries.len()).partition(|&i| scaled[i] < total);
+        while !small.is_empty() && !large.is_empty() {
+            let s = small.pop().unwrap();
+            let l = *large.last().unwrap();
+            threshold[s] = scaled[s];
+            alias[s] = l;
+            scaled[l] = scaled[l] + scaled[s] - total;
+            if scaled[l] < total {
+                large.pop();
+                small.push(l);
+            }
+        }
+
+        Some(AliasTable {
+            names: entries.into_iter().map(|(name, _)| name.clone()).collect(),
+            threshold,
+            alias,
+            total,
+        })
+    }
+
+    pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> &str {
+        let bucket = rng.gen_range(0..self.names.len());
+        if rng.gen_range(0..self.total) < self.threshold[bucket] {
+            &self.names[bucket]
+        } else {
+            &self.names[self.alias[bucket]]
+        }
+    }
+}
+
+fn timed_draws(blockchain: &Blockchain, draws: usize, seed: u64) -> (f64, HashMap<String, usize>) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut counts = HashMap::new();
+    let start = Instant::now();
+    for _ in 0..draws {
+        if let Some(name) = blockchain.select_validator_with(&mut rng) {
+            *counts.entry(name).or_insert(0) += 1;
+        }
+    }
+    (start.elapsed().as_secs_f64(), counts)
+}
+
+// Total variation distance between observed and stake-proportional frequencies, with
+// validators pooled into `groups` buckets so the figure is not dominated by sampling noise.
+fn distance_from_expected(blockchain: &Blockchain, counts: &HashMap<String, usize>, groups: usize) -> f64 {
+    let mut names: Vec<_> = blockchain.nodes.keys().collect();
+    names.sort();
+    let total_stake: u64 = blockchain.nodes.values().sum();
+    let draws: usize = counts.values().sum();
+
+    let mut observed = vec![0.0; groups];
+    let mut expected = vec![0.0; groups];
+    for (i, name) in names.iter().enumerate() {
+        let group = i * groups / names.len();
+        observed[group] += *counts.get(*name).unwrap_or(&0) as f64 / draws as f64;
+        expected[group] += blockchain.nodes[*name] as f64 / total_stake as f64;
+    }
+    observed
+        .iter()
+        .zip(&expected)
+        .map(|(o, e)| (o - e).abs())
+        .sum::<f64>()
+        / 2.0
+}
+
+// Times both strategies on `validators` nodes with random stakes and checks that they
+// produce the same stake-proportional output.
+pub fn benchmark(validators: usize, draws: usize) {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut blockchain = Blockchain::new();
+    for i in 0..validators {
+        blockchain.nodes.insert(format!("validator-{}", i), rng.gen_range(1..=10_000));
+    }
+
+    println!("{} validators, {} draws", validators, draws);
+    for strategy in SelectionStrategy::ALL {
+        blockchain.selection = strategy;
+        let build_start = Instant::now();
+        blockchain.refresh_sampler();
+        let build_secs = build_start.elapsed().as_secs_f64();
+        let (secs, counts) = timed_draws(&blockchain, draws, 1);
+        println!(
+            "  {:?}: {:.3}s ({:.1} ns/draw, {:.3}s setup), distance from stake distribution {:.4}",
+            strategy,
+            secs,
+            secs * 1e9 / draws as f64,
+            build_secs,
+            distance_from_expected(&blockchain, &counts, 100)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alias_table_matches_linear_scan_distribution() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut blockchain = Blockchain::new();
+        for i in 0..200 {
+            blockchain.nodes.insert(format!("validator-{}", i), rng.gen_range(1..=10_000));
+        }
+
+        let mut frequencies = vec![];
+        for strategy in SelectionStrategy::ALL {
+            blockchain.selection = strategy;
+            blockchain.refresh_sampler();
+            let (_, counts) = timed_draws(&blockchain, 100_000, 1);
+            assert!(distance_from_expected(&blockchain, &counts, 20) < 0.02, "{:?} is biased", strategy);
+            frequencies.push(counts);
+        }
+
+        // Total variation distance between the two strategies' per-validator frequencies.
+        let names: Vec<_> = blockchain.nodes.keys().collect();
+        let distance: f64 = names
+            .iter()
+            .map(|name| {
+                let linear = *frequencies[0].get(*name).unwrap_or(&0) as f64;
+                let alias = *frequencies[1].get(*name).unwrap_or(&0) as f64;
+                (linear - alias).abs() / 100_000.0
+            })
+            .sum::<f64>()
+            / 2.0;
+        assert!(distance < 0.05, "strategies differ by {:.4}", distance);
+    }
+}
@@ -0,0 +1,207 @@
+use super::Blockchain;
+use crate::praos::LeaderElection;
+use std::time::Duration;
+
+// Simulated slot/epoch clock. Slot 0 is genesis; `tick` moves to the next slot.
+#[derive(Debug, Clone)]
+pub struct SlotClock {
+    pub slot_duration: Duration,
+    pub slots_per_epoch: u64,
+    current_slot: u64,
+}
+
+impl SlotClock {
+    pub fn new(slot_duration: Duration, slots_per_epoch: u64) -> Self {
+        SlotClock {
+            slot_duration,
+            slots_per_epoch,
+            current_slot: 0,
+        }
+    }
+
+    pub fn current_slot(&self) -> u64 {
+        self.current_slot
+    }
+
+    pub fn advance_to(&mut self, slot: u64) {
+        self.current_slot = self.current_slot.max(slot);
+    }
+
+    pub fn tick(&mut self) -> u64 {
+        self.current_slot += 1;
+        self.current_slot
+    }
+
+    pub fn epoch_of(&self, slot: u64) -> u64 {
+        slot / self.slots_per_epoch
+    }
+
+    pub fn is_epoch_end(&self, slot: u64) -> bool {
+        (slot + 1).is_multiple_of(self.slots_per_epoch)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SlotRecord {
+    pub slot: u64,
+    pub proposer: Option<String>,
+    // None when the proposer was offline and the slot stayed empty.
+    pub block_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Liveness {
+    pub proposed: u64,
+    pub missed: u64,
+    pub consecutive_missed: u64,
+}
+
+impl Blockchain {
+    // Coming back online clears the run of missed proposals, so a validator that is not picked
+    // again soon is not penalized for the time it was away.
+    pub fn set_online(&mut self, validator: &str, online: bool) {
+        if online {
+            self.offline.remove(validator);
+            if let Some(liveness) = self.liveness.get_mut(validator) {
+                liveness.consecutive_missed = 0;
+            }
+        } else {
+            self.offline.insert(validator.to_string());
+        }
+    }
+
+    // Advances the clock by one slot. The selected proposer produces a block if it is online;
+    // otherwise the slot is recorded as empty and counted against the proposer.
+    pub fn next_slot(&mut self) {
+        if self.leader_election == LeaderElection::Praos {
+            self.praos_slot();
+            return;
+        }
+
+        let slot = self.clock.tick();
+        self.refresh_sampler();
+
+        let proposer = self.select_validator();
+        let mut block_id = None;
+        match &proposer {
+            None => println!("Slot {}: no validator selected. Check stakes!", slot),
+            Some(validator) if self.offline.contains(validator) => {
+                let liveness = self.liveness.entry(validator.clone()).or_default();
+                liveness.missed += 1;
+                liveness.consecutive_missed += 1;
+                println!("Slot {}: {} is offline, slot missed", slot, validator);
+            }
+            Some(validator) => {
+                self.append_block(validator.clone());
+                self.distribute_reward(validator, self.block_reward);
+                block_id = self.chain.last().map(|block| block.id);
+                let liveness = self.liveness.entry(validator.clone()).or_default();
+                liveness.proposed += 1;
+                liveness.consecutive_missed = 0;
+                println!("Slot {}: block mined by {}", slot, validator);
+            }
+        }
+        self.slots.push(SlotRecord {
+            slot,
+            proposer,
+            block_id,
+        });
+        self.attest(slot);
+
+        if self.clock.is_epoch_end(slot) {
+            self.apply_inactivity_penalties();
+            self.process_validator_queues(self.clock.epoch_of(slot));
+            self.update_epoch_seed(self.clock.epoch_of(slot) + 1);
+        }
+    }
+
+    // At the end of each epoch, validators that have missed `inactivity_threshold` proposals in a
+    // row lose `inactivity_penalty` percent of their stake until they come back.
+    fn apply_inactivity_penalties(&mut self) {
+        let mut inactive: Vec<_> = self
+            .liveness
+            .iter()
+            .filter(|(_, liveness)| liveness.consecutive_missed >= self.inactivity_threshold)
+            .map(|(name, _)| name.clone())
+            .collect();
+        inactive.sort();
+        for validator in inactive {
+            let penalty = self.slash(&validator, self.inactivity_penalty);
+            println!("Inactivity penalty: {} loses {} stake", validator, penalty);
+        }
+    }
+
+    pub fn liveness_report(&self) {
+        let slots = self.slots.len() as u64;
+        let blocks = self.slots.iter().filter(|record| record.block_id.is_some()).count() as u64;
+        if slots == 0 {
+            println!("No slots processed yet.");
+            return;
+        }
+        let average_block_time = if blocks == 0 {
+            "n/a".to_string()
+        } else {
+            format!("{:.1}s", (self.clock.slot_duration * slots as u32).as_secs_f64() / blocks as f64)
+        };
+        println!(
+            "Epoch {} | {} slots, {} blocks, {} empty ({:.1}% liveness) | average block time {}",
+            self.clock.epoch_of(self.clock.current_slot()),
+            slots,
+            blocks,
+            slots - blocks,
+            blocks as f64 * 100.0 / slots as f64,
+            average_block_time
+        );
+        let empty: Vec<_> = self
+            .slots
+            .iter()
+            .filter(|record| record.block_id.is_none())
+            .map(|record| format!("{} ({})", record.slot, record.proposer.as_deref().unwrap_or("none")))
+            .collect();
+        println!("  empty slots: {:?}", empty);
+
+        let mut names: Vec<_> = self.nodes.keys().collect();
+        names.sort();
+        for name in names {
+            let liveness = self.liveness.get(name).cloned().unwrap_or_default();
+            println!(
+                "  {}: proposed {}, missed {}, missed in a row {}, stake {}",
+                name, liveness.proposed, liveness.missed, liveness.consecutive_missed, self.nodes[name]
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+
+    fn blockchain_with_missed(missed: u64) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for node in [Node::new("Alice", 60), Node::new("Charlie", 40)] {
+            blockchain.register_node(node);
+        }
+        blockchain.set_online("Charlie", false);
+        let liveness = blockchain.liveness.entry("Charlie".to_string()).or_default();
+        liveness.missed = missed;
+        liveness.consecutive_missed = missed;
+        blockchain
+    }
+
+    #[test]
+    fn offline_validator_is_penalized() {
+        let mut blockchain = blockchain_with_missed(4);
+        blockchain.apply_inactivity_penalties();
+        assert_eq!(blockchain.nodes["Charlie"], 38);
+    }
+
+    #[test]
+    fn returning_validator_is_not_penalized() {
+        let mut blockchain = blockchain_with_missed(4);
+        blockchain.set_online("Charlie", true);
+        blockchain.apply_inactivity_penalties();
+        assert_eq!(blockchain.nodes["Charlie"], 40);
+        assert_eq!(blockchain.liveness["Charlie"].missed, 4);
+    }
+}
@@ -0,0 +1,152 @@
+use super::{Blockchain, Node};
+use std::collections::HashMap;
+
+impl Blockchain {
+    // Before genesis, registered nodes form the initial validator set. Afterwards they wait in
+    // the activation queue and only become eligible once an epoch transition lets them in.
+    pub fn register_node(&mut self, node: Node) {
+        if self.chain.is_empty() && self.clock.current_slot() == 0 {
+            self.nodes.insert(node.name, node.stake);
+            self.validator_history.insert(0, self.nodes.clone());
+            self.sampler = None;
+        } else if !self.nodes.contains_key(&node.name)
+            && !self.activation_queue.iter().any(|queued| queued.name == node.name)
+        {
+            self.activation_queue.push_back(node);
+        }
+    }
+
+    // Queues an active validator for exit. It keeps validating until the exit is processed.
+    pub fn request_exit(&mut self, validator: &str) -> bool {
+        if !self.nodes.contains_key(validator) || self.exit_queue.iter().any(|queued| queued == validator) {
+            return false;
+        }
+        self.exit_queue.push_back(validator.to_string());
+        true
+    }
+
+    // Validator-set changes allowed per epoch: a fixed floor, growing with the size of the set.
+    pub fn churn_limit(&self) -> usize {
+        self.min_churn_limit.max(self.nodes.len() / self.churn_limit_quotient)
+    }
+
+    // Runs at the end of `epoch`: activates and exits up to the churn limit each, then records
+    // the validator set that will be active during the next epoch.
+    pub fn process_validator_queues(&mut self, epoch: u64) {
+        let churn = self.churn_limit();
+        for _ in 0..churn {
+            let Some(node) = self.activation_queue.pop_front() else {
+                break;
+            };
+            self.nodes.insert(node.name, node.stake);
+        }
+        for _ in 0..churn {
+            let Some(validator) = self.exit_queue.pop_front() else {
+                break;
+            };
+            self.exit_validator(&validator);
+        }
+        self.sampler = None;
+        self.validator_history.insert(epoch + 1, self.nodes.clone());
+    }
+
+    // Removes the validator, returning its own stake to its balance and bonded delegations to
+    // their owners.
+    fn exit_validator(&mut self, validator: &str) {
+        let self_stake = self.self_stake(validator);
+        self.credit_balance(validator, self_stake);
+        self.nodes.remove(validator);
+        let refunds: Vec<_> = self
+            .delegations
+            .iter()
+            .filter(|((_, v), _)| v == validator)
+            .map(|((delegator, v), amount)| (delegator.clone(), v.clone(), *amount))
+            .collect();
+        for (delegator, v, amount) in refunds {
+            self.delegations.remove(&(delegator.clone(), v));
+            self.credit_balance(&delegator, amount);
+        }
+    }
+
+    // The validator set (and stakes) in force at the start of `epoch`.
+    pub fn validator_set_at(&self, epoch: u64) -> Option<&HashMap<String, u64>> {
+        self.validator_history
+            .range(..=epoch)
+            .next_back()
+            .map(|(_, validators)| validators)
+    }
+
+    pub fn validator_queue_report(&self) {
+        let activations: Vec<_> = self.activation_queue.iter().map(|node| node.name.as_str()).collect();
+        println!(
+            "Churn limit {} per epoch | activation queue {:?} | exit queue {:?}",
+            self.churn_limit(),
+            activations,
+            self.exit_queue
+        );
+        for (epoch, validators) in &self.validator_history {
+            let mut names: Vec<_> = validators.keys().collect();
+            names.sort();
+            println!("  epoch {}: {:?}", epoch, names);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Four genesis validators, so the churn limit is the floor of one per epoch.
+    fn blockchain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for node in [Node::new("Alice", 50), Node::new("Bob", 30), Node::new("Charlie", 20), Node::new("Dave", 10)] {
+            blockchain.register_node(node);
+        }
+        blockchain.clock.tick();
+        blockchain
+    }
+
+    #[test]
+    fn churn_limit_caps_changes_per_epoch() {
+        let mut blockchain = blockchain();
+        assert_eq!(blockchain.churn_limit(), 1);
+        for name in ["Erin", "Frank"] {
+            blockchain.register_node(Node::new(name, 15));
+        }
+        blockchain.request_exit("Alice");
+        blockchain.request_exit("Bob");
+
+        blockchain.process_validator_queues(0);
+        assert!(blockchain.nodes.contains_key("Erin") && !blockchain.nodes.contains_key("Frank"));
+        assert!(!blockchain.nodes.contains_key("Alice") && blockchain.nodes.contains_key("Bob"));
+        blockchain.process_validator_queues(1);
+        assert!(blockchain.nodes.contains_key("Frank") && !blockchain.nodes.contains_key("Bob"));
+    }
+
+    #[test]
+    fn validator_set_at_returns_the_set_in_force() {
+        let mut blockchain = blockchain();
+        blockchain.register_node(Node::new("Erin", 15));
+        blockchain.process_validator_queues(2);
+
+        for epoch in [0, 1, 2] {
+            assert!(!blockchain.validator_set_at(epoch).unwrap().contains_key("Erin"));
+        }
+        for epoch in [3, 10] {
+            assert_eq!(blockchain.validator_set_at(epoch).unwrap()["Erin"], 15);
+        }
+    }
+
+    #[test]
+    fn exit_returns_own_stake_and_delegations() {
+        let mut blockchain = blockchain();
+        blockchain.credit_balance("Erin", 25);
+        blockchain.delegate("Erin", "Alice", 20).unwrap();
+        blockchain.request_exit("Alice");
+        blockchain.process_validator_queues(0);
+
+        assert_eq!(blockchain.balances["Alice"], 50);
+        assert_eq!(blockchain.balances["Erin"], 25);
+        assert!(blockchain.delegations.is_empty());
+    }
+}